@@ -69,7 +69,7 @@ pub fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4
    let near = 0.1;
    let far = 1000.0;
 
-   perspective(fov, aspect_ratio, near, far)
+   perspective(aspect_ratio, fov, near, far)
 }
 
 pub fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
@@ -8,6 +8,9 @@ pub enum ShaderType {
    Moon,
    RingedPlanet,
    Starfield,
+   // Shader propio registrado en Uniforms::custom_shaders, por índice.
+   // Un índice sin shader registrado se dibuja negro (transparente), sin entrar en pánico
+   Custom(usize),
 }
 
 pub struct CelestialBody {
@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
 r: u8,
 g: u8,
@@ -35,8 +35,87 @@ pub fn from_hex(hex: u32) -> Self {
    Color { r, g, b }
 }
 
+// Linear interpolation toward `other`, t clamped to 0.0..=1.0
+pub fn lerp(&self, other: &Color, t: f32) -> Color {
+   let t = t.clamp(0.0, 1.0);
+   let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+   Color {
+   r: mix(self.r, other.r),
+   g: mix(self.g, other.g),
+   b: mix(self.b, other.b),
+   }
+}
+
+// Relative luminance (Rec. 709) in the 0.0 to 1.0 range
+pub fn luminance(&self) -> f32 {
+   (0.2126 * self.r as f32 + 0.7152 * self.g as f32 + 0.0722 * self.b as f32) / 255.0
+}
+
+// Pushes each channel away from (amount > 0) or toward (amount < 0) the luminance,
+// which stays the same; amount 0 is identity
+pub fn saturate_by(&self, amount: f32) -> Color {
+   let gray = self.luminance() * 255.0;
+   let push = |channel: u8| (gray + (channel as f32 - gray) * (1.0 + amount)).round().clamp(0.0, 255.0) as u8;
+   Color {
+   r: push(self.r),
+   g: push(self.g),
+   b: push(self.b),
+   }
+}
+
+// Warms (positive, toward orange) or cools (negative, toward blue) the color.
+// A shift of 1000.0 is a mild grade; luminance is roughly preserved. 0 is identity.
+pub fn temperature_tint(&self, kelvin_shift: f32) -> Color {
+   if kelvin_shift == 0.0 {
+      return *self;
+   }
+   let amount = (kelvin_shift / 5000.0).clamp(-1.0, 1.0);
+   let r = self.r as f32 * (1.0 + 0.4 * amount);
+   let g = self.g as f32 * (1.0 + 0.1 * amount);
+   let b = self.b as f32 * (1.0 - 0.4 * amount);
+
+   let tinted_luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+   let scale = if tinted_luminance > 0.0 { self.luminance() * 255.0 / tinted_luminance } else { 1.0 };
+   Color::from_float(r * scale / 255.0, g * scale / 255.0, b * scale / 255.0)
+}
+
+// Channel-wise product, as when light of color `other` hits a surface of this color.
+// White is identity, black gives black
+pub fn modulate(&self, other: &Color) -> Color {
+   let product = |a: u8, b: u8| ((a as u32 * b as u32 + 127) / 255) as u8;
+   Color {
+   r: product(self.r, other.r),
+   g: product(self.g, other.g),
+   b: product(self.b, other.b),
+   }
+}
+
+// Black or white, whichever contrasts more with this color (WCAG contrast ratio),
+// for labels and overlays drawn on top of a render
+pub fn contrast_against(&self) -> Color {
+   let luminance = self.luminance();
+   let contrast_with_white = 1.05 / (luminance + 0.05);
+   let contrast_with_black = (luminance + 0.05) / 0.05;
+   if contrast_with_white >= contrast_with_black {
+      Color::new(255, 255, 255)
+   } else {
+      Color::black()
+   }
+}
+
+// Snaps each channel to the nearest of `levels` evenly spaced values (minimum 2)
+pub fn posterize(&self, levels: u32) -> Color {
+   let step = 255.0 / (levels.max(2) - 1) as f32;
+   let snap = |channel: u8| ((channel as f32 / step).round() * step).round() as u8;
+   Color {
+   r: snap(self.r),
+   g: snap(self.g),
+   b: snap(self.b),
+   }
+}
+
 // Function to return the color as a hex value
-pub fn to_hex(&self) -> u32 {
+pub fn to_hex(self) -> u32 {
    ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
 }
 }
@@ -77,3 +156,91 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
    write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
 }
 }
+
+#[cfg(test)]
+mod tests {
+use super::*;
+
+// Every color on a coarse grid of the RGB cube
+fn sample_colors() -> Vec<Color> {
+   let steps = [0u8, 37, 64, 101, 128, 155, 200, 231, 255];
+   let mut colors = Vec::new();
+   for r in steps {
+      for g in steps {
+         for b in steps {
+            colors.push(Color::new(r, g, b));
+         }
+      }
+   }
+   colors
+}
+
+#[test]
+fn posterize_two_levels_gives_eight_colors() {
+   let mut distinct: Vec<u32> = sample_colors().iter().map(|color| color.posterize(2).to_hex()).collect();
+   distinct.sort();
+   distinct.dedup();
+   assert_eq!(distinct.len(), 8);
+   assert!(distinct.iter().all(|hex| [0x00, 0xFF].contains(&(hex >> 16)) && [0x00, 0xFF].contains(&(hex & 0xFF))));
+}
+
+#[test]
+fn posterize_256_levels_is_identity() {
+   for color in sample_colors() {
+      assert_eq!(color.posterize(256), color);
+   }
+}
+
+#[test]
+fn saturate_by_keeps_luminance_and_zero_is_identity() {
+   for color in sample_colors() {
+      assert_eq!(color.saturate_by(0.0), color);
+   }
+   // Muted colors, far from clipping a channel when pushed
+   for color in [Color::new(120, 100, 90), Color::new(90, 110, 140), Color::new(128, 128, 128), Color::new(150, 120, 100)] {
+      for amount in [-0.5, 0.3, 0.6] {
+         let adjusted = color.saturate_by(amount);
+         assert!((adjusted.luminance() - color.luminance()).abs() < 0.01, "{color:?} by {amount}: {adjusted:?}");
+      }
+   }
+   // And saturation really changes: the strongest channel moves away from the weakest
+   let muted = Color::new(150, 120, 100);
+   let spread = |color: Color| color.r as i32 - color.b as i32;
+   assert!(spread(muted.saturate_by(0.6)) > spread(muted));
+   assert!(spread(muted.saturate_by(-0.5)) < spread(muted));
+}
+
+#[test]
+fn temperature_tint_warms_cools_and_zero_is_identity() {
+   for color in sample_colors() {
+      assert_eq!(color.temperature_tint(0.0), color);
+   }
+   let red_minus_blue = |color: Color| color.r as i32 - color.b as i32;
+   for color in [Color::new(128, 128, 128), Color::new(90, 110, 140), Color::new(150, 120, 100)] {
+      let warm = color.temperature_tint(2000.0);
+      let cool = color.temperature_tint(-2000.0);
+      assert!(red_minus_blue(warm) > red_minus_blue(color), "{color:?} -> {warm:?}");
+      assert!(red_minus_blue(cool) < red_minus_blue(color), "{color:?} -> {cool:?}");
+      assert!((warm.luminance() - color.luminance()).abs() < 0.02);
+   }
+}
+
+#[test]
+fn contrast_against_picks_white_on_dark_and_black_on_bright() {
+   let white = Color::new(255, 255, 255);
+   // Night side of a rocky planet and a bright gas-giant band
+   assert_eq!(Color::from_hex(0x1a0f0a).contrast_against(), white);
+   assert_eq!(Color::from_hex(0xe6c9a8).contrast_against(), Color::black());
+
+   // Black and white contrast equally at a luminance of about 0.18
+   let threshold = 0.0525f32.sqrt() - 0.05;
+   for color in sample_colors() {
+      let luminance = color.luminance();
+      if luminance < threshold - 0.01 {
+         assert_eq!(color.contrast_against(), white, "{color:?}");
+      } else if luminance > threshold + 0.01 {
+         assert_eq!(color.contrast_against(), Color::black(), "{color:?}");
+      }
+   }
+}
+}
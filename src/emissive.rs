@@ -0,0 +1,111 @@
+use crate::color::Color;
+
+// Acumulador de capas emisivas (plasma, granulación, corona, destellos).
+// Las capas se suman en floats sin recortar y se comprimen al rango de pantalla
+// una sola vez al final, en vez de encadenar mezclas con factores sueltos:
+// sumar una capa siempre aclara, pero el resultado nunca satura de golpe.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EmissiveAccumulator {
+   r: f32,
+   g: f32,
+   b: f32,
+}
+
+// Hasta este valor el tone mapping no cambia nada; por encima comprime suavemente hacia 1.0
+const TONE_MAP_KNEE: f32 = 0.8;
+
+fn channels(color: Color) -> (f32, f32, f32) {
+   let hex = color.to_hex();
+   (
+      ((hex >> 16) & 0xFF) as f32 / 255.0,
+      ((hex >> 8) & 0xFF) as f32 / 255.0,
+      (hex & 0xFF) as f32 / 255.0,
+   )
+}
+
+fn tone_map_channel(value: f32) -> f32 {
+   if value <= TONE_MAP_KNEE {
+      value.max(0.0)
+   } else {
+      let headroom = 1.0 - TONE_MAP_KNEE;
+      TONE_MAP_KNEE + headroom * (1.0 - (-(value - TONE_MAP_KNEE) / headroom).exp())
+   }
+}
+
+impl EmissiveAccumulator {
+   // Empieza desde un color base (la emisión propia de la superficie)
+   pub fn from_color(color: Color) -> Self {
+      let (r, g, b) = channels(color);
+      EmissiveAccumulator { r, g, b }
+   }
+
+   // Suma una capa de luz: `strength` 1.0 agrega el color completo
+   pub fn add(&mut self, color: Color, strength: f32) {
+      let (r, g, b) = channels(color);
+      let strength = strength.max(0.0);
+      self.r += r * strength;
+      self.g += g * strength;
+      self.b += b * strength;
+   }
+
+   // Mezcla hacia un color (para lo que tapa la emisión, como las manchas), amount en 0..1
+   pub fn mix(&mut self, color: Color, amount: f32) {
+      let (r, g, b) = channels(color);
+      let amount = amount.clamp(0.0, 1.0);
+      self.r += (r - self.r) * amount;
+      self.g += (g - self.g) * amount;
+      self.b += (b - self.b) * amount;
+   }
+
+   // Multiplica toda la luz acumulada (control general de la emisión de un cuerpo)
+   pub fn scale(&mut self, factor: f32) {
+      let factor = factor.max(0.0);
+      self.r *= factor;
+      self.g *= factor;
+      self.b *= factor;
+   }
+
+   // Valores lineales sin comprimir (pueden pasar de 1.0), para exportar en HDR
+   pub fn linear_rgb(&self) -> [f32; 3] {
+      [self.r, self.g, self.b]
+   }
+
+   pub fn tone_map(&self) -> Color {
+      Color::from_float(
+         tone_map_channel(self.r),
+         tone_map_channel(self.g),
+         tone_map_channel(self.b),
+      )
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn two_additive_layers_are_brighter_but_not_clipped() {
+      let layer = |color: u32, strength: f32| {
+         let mut emission = EmissiveAccumulator::default();
+         emission.add(Color::from_hex(color), strength);
+         emission
+      };
+      let plasma = layer(0xFFAA00, 0.7);
+      let granulation = layer(0xFFF3B0, 0.6);
+      let mut both = plasma;
+      both.add(Color::from_hex(0xFFF3B0), 0.6);
+
+      // En lineal la suma pasa de 1.0 en rojo; tras el tone mapping sigue debajo del máximo
+      assert!(both.linear_rgb()[0] > 1.0);
+      let combined = both.tone_map();
+      let (r, g, b) = channels(combined);
+      assert!(r < 1.0 && g < 1.0 && b < 1.0, "{combined:?} clipped");
+
+      for alone in [plasma, granulation] {
+         let alone = alone.tone_map();
+         let (ar, ag, ab) = channels(alone);
+         assert!(combined.luminance() > alone.luminance());
+         assert!(r >= ar && g >= ag && b >= ab, "{combined:?} darker than {alone:?} in some channel");
+      }
+   }
+}
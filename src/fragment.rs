@@ -5,9 +5,36 @@ pub struct Fragment {
    pub position: Vec2,
    pub color: Color,
    pub depth: f32,
+   // Vector cero = el rasterizador no dio normal (ver normal_or_sphere)
    pub normal: Vec3,
+   // Tangente interpolada en espacio mundo (cero si no hay); con la normal arma la base de los normal maps
+   pub tangent: Vec3,
+   // Posición en espacio objeto: estable mientras el cuerpo rota, úsala para el noise
    pub vertex_position: Vec3,
+   // Posición en espacio mundo (tras la model matrix): para sombras entre cuerpos y luces puntuales
+   pub world_position: Vec3,
+   // Coordenadas de textura interpoladas (cero si la malla no trae UVs)
+   pub tex_coords: Vec2,
    pub intensity: f32,
+   // Oclusión ambiental: 1.0 = expuesto, valores bajos oscurecen fondos de cráteres y valles
+   pub ao: f32,
+}
+
+impl Default for Fragment {
+   fn default() -> Self {
+      Fragment {
+         position: Vec2::new(0.0, 0.0),
+         color: Color::new(255, 255, 255),
+         depth: 0.0,
+         normal: Vec3::new(0.0, 0.0, 0.0),
+         tangent: Vec3::new(0.0, 0.0, 0.0),
+         vertex_position: Vec3::new(0.0, 0.0, 0.0),
+         world_position: Vec3::new(0.0, 0.0, 0.0),
+         tex_coords: Vec2::new(0.0, 0.0),
+         intensity: 1.0,
+         ao: 1.0,
+      }
+   }
 }
 
 impl Fragment {
@@ -16,9 +43,7 @@ impl Fragment {
          position: Vec2::new(x, y),
          color,
          depth,
-         normal: Vec3::new(0.0, 0.0, 0.0),
-         vertex_position: Vec3::new(0.0, 0.0, 0.0),
-         intensity: 1.0,
+         ..Default::default()
       }
    }
 
@@ -36,8 +61,108 @@ impl Fragment {
          color,
          depth,
          normal,
+         tangent: Vec3::new(0.0, 0.0, 0.0),
          vertex_position,
+         world_position: vertex_position,
+         tex_coords: Vec2::new(0.0, 0.0),
          intensity,
+         ao: 1.0,
+      }
+   }
+
+   // Builder: Fragment::default().with_position(x, y).with_intensity(0.5)...
+   // Los campos que no se indiquen conservan su valor por defecto.
+   pub fn with_position(mut self, x: f32, y: f32) -> Self {
+      self.position = Vec2::new(x, y);
+      self
+   }
+
+   pub fn with_depth(mut self, depth: f32) -> Self {
+      self.depth = depth;
+      self
+   }
+
+   pub fn with_normal(mut self, normal: Vec3) -> Self {
+      self.normal = normal;
+      self
+   }
+
+   pub fn with_tangent(mut self, tangent: Vec3) -> Self {
+      self.tangent = tangent;
+      self
+   }
+
+   pub fn with_vertex_position(mut self, vertex_position: Vec3) -> Self {
+      self.vertex_position = vertex_position;
+      self
+   }
+
+   pub fn with_world_position(mut self, world_position: Vec3) -> Self {
+      self.world_position = world_position;
+      self
+   }
+
+   pub fn with_tex_coords(mut self, tex_coords: Vec2) -> Self {
+      self.tex_coords = tex_coords;
+      self
+   }
+
+   pub fn with_intensity(mut self, intensity: f32) -> Self {
+      self.intensity = intensity;
+      self
+   }
+
+   pub fn with_ao(mut self, ao: f32) -> Self {
+      self.ao = ao;
+      self
+   }
+
+   // La normal dada, o si no hay, la de una esfera centrada en el origen:
+   // para un cuerpo esférico coincide con la posición normalizada
+   pub fn normal_or_sphere(&self) -> Vec3 {
+      if self.normal.norm_squared() > 0.0 {
+         self.normal
+      } else if self.vertex_position.norm_squared() > 0.0 {
+         self.vertex_position.normalize()
+      } else {
+         Vec3::new(0.0, 0.0, 1.0)
       }
    }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn builder_keeps_defaults_for_unset_fields() {
+      let fragment = Fragment::default().with_position(3.0, 4.0).with_intensity(0.25);
+      assert_eq!(fragment.position, Vec2::new(3.0, 4.0));
+      assert_eq!(fragment.intensity, 0.25);
+
+      assert_eq!(fragment.depth, 0.0);
+      assert_eq!(fragment.color, Color::new(255, 255, 255));
+      assert_eq!(fragment.normal, Vec3::zeros());
+      assert_eq!(fragment.vertex_position, Vec3::zeros());
+      assert_eq!(fragment.world_position, Vec3::zeros());
+      assert_eq!(fragment.tex_coords, Vec2::zeros());
+      assert_eq!(fragment.ao, 1.0);
+      // Sin normal ni posición, la normal de respaldo mira a la cámara
+      assert_eq!(fragment.normal_or_sphere(), Vec3::new(0.0, 0.0, 1.0));
+   }
+
+   #[test]
+   fn normal_or_sphere_falls_back_to_the_normalized_position() {
+      let point = Vec3::new(0.6, -0.48, 0.64);
+      let bare = Fragment::default().with_vertex_position(point);
+      assert!((bare.normal_or_sphere() - point.normalize()).norm() < 1e-6);
+
+      // Fuera de la esfera unitaria también sale normalizada
+      let scaled = Fragment::default().with_vertex_position(point * 3.0);
+      assert!((scaled.normal_or_sphere() - point.normalize()).norm() < 1e-6);
+
+      // Una normal explícita tiene prioridad
+      let explicit = Vec3::new(0.0, 1.0, 0.0);
+      assert_eq!(bare.with_normal(explicit).normal_or_sphere(), explicit);
+   }
+}
@@ -1,5 +1,7 @@
 // framebuffer.rs
 
+use crate::color::Color;
+
 pub struct Framebuffer {
    pub width: usize,
    pub height: usize,
@@ -40,6 +42,21 @@ impl Framebuffer {
       }
    }
 
+   // Keeps the nearest fragment per pixel, so several bodies can be shaded into
+   // the same framebuffer in any order:
+   //    let ctx = FrameContext::new(&uniforms);
+   //    let color = fragment_shader(&fragment, &ctx, &shader_type);
+   //    framebuffer.put_pixel(x, y, fragment.depth, color);
+   pub fn put_pixel(&mut self, x: usize, y: usize, depth: f32, color: Color) {
+      if x < self.width && y < self.height {
+         let index = y * self.width + x;
+         if self.zbuffer[index] > depth {
+               self.buffer[index] = color.to_hex();
+               self.zbuffer[index] = depth;
+         }
+      }
+   }
+
    pub fn set_background_color(&mut self, color: u32) {
       self.background_color = color;
    }
@@ -48,3 +65,24 @@ impl Framebuffer {
       self.current_color = color;
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn nearest_pixel_wins_in_either_order() {
+      let (red, blue) = (Color::new(255, 0, 0), Color::new(0, 0, 255));
+
+      let mut far_first = Framebuffer::new(4, 4);
+      far_first.put_pixel(1, 2, 10.0, red);
+      far_first.put_pixel(1, 2, 1.0, blue);
+      assert_eq!(far_first.buffer[2 * 4 + 1], blue.to_hex());
+
+      let mut near_first = Framebuffer::new(4, 4);
+      near_first.put_pixel(1, 2, 1.0, blue);
+      near_first.put_pixel(1, 2, 10.0, red);
+      assert_eq!(near_first.buffer[2 * 4 + 1], blue.to_hex());
+      assert_eq!(near_first.zbuffer[2 * 4 + 1], 1.0);
+   }
+}
@@ -0,0 +1,43 @@
+use crate::color::Color;
+
+// Degradado de colores con paradas en posiciones de 0.0 a 1.0
+#[derive(Debug, Clone)]
+pub struct Gradient {
+   stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+   // Las paradas se ordenan por posición; un degradado sin paradas es negro
+   pub fn new(mut stops: Vec<(f32, Color)>) -> Self {
+      stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+      Gradient { stops }
+   }
+
+   // Degradado uniforme entre dos colores
+   pub fn between(from: Color, to: Color) -> Self {
+      Gradient::new(vec![(0.0, from), (1.0, to)])
+   }
+
+   pub fn sample(&self, t: f32) -> Color {
+      let (first, last) = match (self.stops.first(), self.stops.last()) {
+         (Some(first), Some(last)) => (first, last),
+         _ => return Color::black(),
+      };
+      if t <= first.0 {
+         return first.1;
+      }
+      if t >= last.0 {
+         return last.1;
+      }
+
+      for pair in self.stops.windows(2) {
+         let (start, end) = (pair[0], pair[1]);
+         if t <= end.0 {
+            let span = end.0 - start.0;
+            let local_t = if span > 0.0 { (t - start.0) / span } else { 1.0 };
+            return start.1.lerp(&end.1, local_t);
+         }
+      }
+      last.1
+   }
+}
@@ -0,0 +1,18 @@
+pub mod framebuffer;
+pub mod triangle;
+pub mod vertex;
+pub mod obj;
+pub mod color;
+pub mod gradient;
+pub mod emissive;
+pub mod fragment;
+pub mod shaders;
+pub mod params;
+pub mod math;
+pub mod render;
+pub mod sphere;
+pub mod preview;
+pub mod postprocess;
+pub mod scene;
+pub mod camera;
+pub mod celestial_body;
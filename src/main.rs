@@ -3,23 +3,14 @@ use minifb::{Key, Window, WindowOptions};
 use std::time::Duration;
 use std::f32::consts::PI;
 
-mod framebuffer;
-mod triangle;
-mod vertex;
-mod obj;
-mod color;
-mod fragment;
-mod shaders;
-mod camera;
-mod celestial_body;
-
-use framebuffer::Framebuffer;
-use vertex::Vertex;
-use obj::Obj;
-use triangle::triangle;
-use camera::Camera;
-use shaders::{vertex_shader, fragment_shader, Uniforms};
-use celestial_body::{CelestialBody, ShaderType};
+use space_renderer::framebuffer::Framebuffer;
+use space_renderer::obj::Obj;
+use space_renderer::camera::Camera;
+use space_renderer::shaders::Uniforms;
+use space_renderer::params::ShaderParams;
+use space_renderer::render::{render, create_model_matrix};
+use space_renderer::postprocess::posterize;
+use space_renderer::celestial_body::{CelestialBody, ShaderType};
 
 
 pub struct RenderContext {
@@ -28,54 +19,50 @@ pub struct RenderContext {
     bodies: Vec<CelestialBody>,
     current_body_index: usize,
     time: f32,
+    // Se copian a los Uniforms de cada frame, así pueden cambiarse en caliente
+    params: ShaderParams,
+    // Pasada final de posterizado (look retro)
+    retro_mode: bool,
 }
 
 impl RenderContext {
     fn new(width: usize, height: usize) -> Self {
-        let mut bodies = Vec::new();
-        
-        // Sol en el centro
-        bodies.push(
+        let bodies = vec![
+            // Sol en el centro
             CelestialBody::new(
                 Vec3::new(0.0, 0.0, 0.0),
                 1.5,
                 ShaderType::Sun,
             )
-            .with_rotation_speed(Vec3::new(0.0, 0.1, 0.0))
-        );
-        
-        // Planeta rocoso (tipo Tierra)
-        bodies.push(
+            .with_rotation_speed(Vec3::new(0.0, 0.1, 0.0)),
+            
+            // Planeta rocoso (tipo Tierra)
             CelestialBody::new(
                 Vec3::new(3.0, 0.0, 0.0),
                 0.5,
                 ShaderType::RockyPlanet,
             )
             .with_orbit(3.0, 0.5)
-            .with_rotation_speed(Vec3::new(0.0, 0.5, 0.0))
-        );
-        
-        // Luna del planeta rocoso
-        bodies.push(
+            .with_rotation_speed(Vec3::new(0.0, 0.5, 0.0)),
+            
+            // Luna del planeta rocoso
             CelestialBody::new(
                 Vec3::new(3.8, 0.0, 0.0), // Cerca del planeta rocoso
                 0.15, // Más pequeña que el planeta
                 ShaderType::Moon,
             )
             .with_orbit(0.8, 1.2) // Órbita alrededor del planeta rocoso
-            .with_rotation_speed(Vec3::new(0.0, 0.3, 0.0))
-        );
-        
-        // Gigante gaseoso (tipo Júpiter)
-        bodies.push(
+            .with_rotation_speed(Vec3::new(0.0, 0.3, 0.0)),
+            
+            // Gigante gaseoso (tipo Júpiter)
             CelestialBody::new(
                 Vec3::new(6.0, 0.0, 0.0),
                 0.8, 
                 ShaderType::GasGiant,
             )
             .with_orbit(6.0, 0.25)
-            .with_rotation_speed(Vec3::new(0.0, 0.8, 0.0))
-        );
+            .with_rotation_speed(Vec3::new(0.0, 0.8, 0.0)),
+        ];
         
         // Temporalmente removido el campo de estrellas para debug
 
@@ -89,48 +76,12 @@ impl RenderContext {
             bodies,
             current_body_index: 0,
             time: 0.0,
+            params: ShaderParams::default(),
+            retro_mode: false,
         }
     }
 }
 
-fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
-    let (sin_x, cos_x) = rotation.x.sin_cos();
-    let (sin_y, cos_y) = rotation.y.sin_cos();
-    let (sin_z, cos_z) = rotation.z.sin_cos();
-
-    let rotation_matrix_x = Mat4::new(
-        1.0,  0.0,    0.0,   0.0,
-        0.0,  cos_x, -sin_x, 0.0,
-        0.0,  sin_x,  cos_x, 0.0,
-        0.0,  0.0,    0.0,   1.0,
-    );
-
-    let rotation_matrix_y = Mat4::new(
-        cos_y,  0.0,  sin_y, 0.0,
-        0.0,    1.0,  0.0,   0.0,
-        -sin_y, 0.0,  cos_y, 0.0,
-        0.0,    0.0,  0.0,   1.0,
-    );
-
-    let rotation_matrix_z = Mat4::new(
-        cos_z, -sin_z, 0.0, 0.0,
-        sin_z,  cos_z, 0.0, 0.0,
-        0.0,    0.0,   1.0, 0.0,
-        0.0,    0.0,   0.0, 1.0,
-    );
-
-    let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
-
-    let transform_matrix = Mat4::new(
-        scale, 0.0,   0.0,   translation.x,
-        0.0,   scale, 0.0,   translation.y,
-        0.0,   0.0,   scale, translation.z,
-        0.0,   0.0,   0.0,   1.0,
-    );
-
-    transform_matrix * rotation_matrix
-}
-
 fn create_view_matrix(camera: &Camera) -> Mat4 {
     look_at(&camera.eye, &camera.center, &camera.up)
 }
@@ -141,7 +92,7 @@ fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
     let near = 0.1;
     let far = 1000.0;
 
-    perspective(fov, aspect_ratio, near, far)
+    perspective(aspect_ratio, fov, near, far)
 }
 
 fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
@@ -153,53 +104,6 @@ fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
     )
 }
 
-fn render(
-    framebuffer: &mut Framebuffer,
-    uniforms: &Uniforms,
-    vertex_array: &[Vertex],
-    shader_type: &ShaderType,
-) {
-    // Vertex Shader Stage
-    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let transformed = vertex_shader(vertex, uniforms);
-        transformed_vertices.push(transformed);
-    }
-
-    // Primitive Assembly Stage
-    let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
-        }
-    }
-
-    // Rasterization Stage
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
-    }
-
-    // Fragment Processing Stage
-    for fragment in fragments {
-        let x = fragment.position.x as usize;
-        let y = fragment.position.y as usize;
-        
-        if x < framebuffer.width && y < framebuffer.height {
-            // Apply fragment shader
-            let shaded_color = fragment_shader(&fragment, uniforms, shader_type);
-            let color = shaded_color.to_hex();
-            
-            framebuffer.set_current_color(color);
-            framebuffer.point(x, y, fragment.depth);
-        }
-    }
-}
-
 fn main() {
     let window_width = 800;
     let window_height = 600;
@@ -238,6 +142,7 @@ fn main() {
     println!("  3: Focus on Moon");
     println!("  4: Focus on Jupiter (Gas Giant)");
     println!("  Space: Toggle orbit animation");
+    println!("  P: Toggle retro (posterize) mode");
     println!("  ESC: Exit");
 
     let mut orbit_enabled = true;
@@ -311,13 +216,14 @@ fn main() {
                 body.rotation,
             );
 
-            let uniforms = Uniforms::new(
+            let mut uniforms = Uniforms::new(
                 model_matrix,
                 view_matrix,
                 projection_matrix,
                 viewport_matrix,
                 body.time,
             );
+            uniforms.update_params(context.params.clone());
 
             render(
                 &mut context.framebuffer,
@@ -327,6 +233,10 @@ fn main() {
             );
         }
 
+        if context.retro_mode {
+            posterize(&mut context.framebuffer, 4);
+        }
+
         window
             .update_with_buffer(
                 &context.framebuffer.buffer,
@@ -393,4 +303,10 @@ fn handle_input(window: &Window, context: &mut RenderContext, orbit_enabled: &mu
         *orbit_enabled = !*orbit_enabled;
         println!("Orbit animation: {}", if *orbit_enabled { "ON" } else { "OFF" });
     }
+
+    // Toggle retro mode
+    if window.is_key_pressed(Key::P, minifb::KeyRepeat::No) {
+        context.retro_mode = !context.retro_mode;
+        println!("Retro mode: {}", if context.retro_mode { "ON" } else { "OFF" });
+    }
 }
\ No newline at end of file
@@ -0,0 +1,85 @@
+// Funciones de interpolación compartidas por los shaders
+
+// Limita x al rango [0, 1]
+pub fn saturate(x: f32) -> f32 {
+   x.clamp(0.0, 1.0)
+}
+
+// Transición suave (Hermite) de 0 a 1 cuando x va de a hasta b
+pub fn smoothstep(a: f32, b: f32, x: f32) -> f32 {
+   let t = saturate((x - a) / (b - a));
+   t * t * (3.0 - 2.0 * t)
+}
+
+// Mapeo lineal de [in_min, in_max] a [out_min, out_max], sin limitar el resultado
+pub fn remap(x: f32, in_min: f32, in_max: f32, out_min: f32, out_max: f32) -> f32 {
+   out_min + (x - in_min) / (in_max - in_min) * (out_max - out_min)
+}
+
+// Formas de combinar dos valores de noise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseBlend {
+   Min,
+   Max,
+   Average,
+   Multiply,
+}
+
+pub fn blend_noise(a: f32, b: f32, mode: NoiseBlend) -> f32 {
+   match mode {
+      NoiseBlend::Min => a.min(b),
+      NoiseBlend::Max => a.max(b),
+      NoiseBlend::Average => (a + b) * 0.5,
+      NoiseBlend::Multiply => a * b,
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn saturate_clamps_to_unit_range() {
+      assert_eq!(saturate(-0.5), 0.0);
+      assert_eq!(saturate(0.0), 0.0);
+      assert_eq!(saturate(0.5), 0.5);
+      assert_eq!(saturate(1.0), 1.0);
+      assert_eq!(saturate(1.5), 1.0);
+   }
+
+   #[test]
+   fn smoothstep_is_flat_outside_and_half_at_midpoint() {
+      assert_eq!(smoothstep(2.0, 4.0, 1.0), 0.0);
+      assert_eq!(smoothstep(2.0, 4.0, 2.0), 0.0);
+      assert_eq!(smoothstep(2.0, 4.0, 3.0), 0.5);
+      assert_eq!(smoothstep(2.0, 4.0, 4.0), 1.0);
+      assert_eq!(smoothstep(2.0, 4.0, 5.0), 1.0);
+      // Con los bordes invertidos la rampa baja
+      assert_eq!(smoothstep(4.0, 2.0, 2.0), 1.0);
+      assert_eq!(smoothstep(4.0, 2.0, 3.0), 0.5);
+      // Pendiente nula en los extremos: pasos chicos cerca del borde casi no cambian el valor
+      assert!(smoothstep(0.0, 1.0, 0.01) < 0.001);
+   }
+
+   #[test]
+   fn remap_maps_ends_and_midpoint_without_clamping() {
+      assert_eq!(remap(0.0, 0.0, 1.0, 10.0, 20.0), 10.0);
+      assert_eq!(remap(0.5, 0.0, 1.0, 10.0, 20.0), 15.0);
+      assert_eq!(remap(1.0, 0.0, 1.0, 10.0, 20.0), 20.0);
+      assert_eq!(remap(2.0, 0.0, 1.0, 10.0, 20.0), 30.0);
+      assert_eq!(remap(0.25, 0.0, 1.0, 1.0, -1.0), 0.5);
+   }
+
+   #[test]
+   fn noise_blend_modes_at_known_inputs() {
+      assert_eq!(blend_noise(0.4, -0.6, NoiseBlend::Min), -0.6);
+      assert_eq!(blend_noise(0.4, -0.6, NoiseBlend::Max), 0.4);
+      assert!((blend_noise(0.4, -0.6, NoiseBlend::Average) + 0.1).abs() < 1e-6);
+      assert!((blend_noise(0.4, -0.6, NoiseBlend::Multiply) + 0.24).abs() < 1e-6);
+      // Con valores iguales todos los modos salvo Multiply devuelven el mismo valor
+      for mode in [NoiseBlend::Min, NoiseBlend::Max, NoiseBlend::Average] {
+         assert_eq!(blend_noise(0.5, 0.5, mode), 0.5);
+      }
+      assert_eq!(blend_noise(0.5, 0.5, NoiseBlend::Multiply), 0.25);
+   }
+}
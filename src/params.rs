@@ -0,0 +1,367 @@
+// Parámetros ajustables de los shaders, agrupados por tipo de cuerpo.
+// Los valores por defecto reproducen el aspecto original de cada shader.
+
+use crate::color::Color;
+use crate::gradient::Gradient;
+use crate::math::NoiseBlend;
+use nalgebra_glm::Vec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Debug, Clone, Default)]
+pub struct ShaderParams {
+   pub sun: SunParams,
+   pub rocky: RockyPlanetParams,
+   pub gas_giant: GasGiantParams,
+   pub moon: MoonParams,
+   pub rings: RingParams,
+}
+
+#[derive(Debug, Clone)]
+pub struct SunParams {
+   // Cuánto se aclaran/oscurecen las celdas de granulación
+   pub granulation_strength: f32,
+   // Parpadeos por unidad de tiempo de cada celda (0 = celdas estáticas)
+   pub granulation_flicker_speed: f32,
+   // Color de la penumbra (anillo exterior de la mancha) y de la umbra (centro)
+   pub spot_color: Color,
+   pub umbra_color: Color,
+   // Cuánto oscurecen las manchas (0 = invisibles)
+   pub spot_darkness: f32,
+   // Desde qué fracción de la mancha empieza la umbra (0..1)
+   pub umbra_threshold: f32,
+   // Deriva de las manchas en el espacio del noise por unidad de tiempo
+   pub spot_drift_velocity: Vec3,
+   // Radio del disco brillante como fracción del radio aparente de la esfera;
+   // lo que queda afuera es corona que se apaga hacia el borde (None = disco completo)
+   pub photosphere_radius: Option<f32>,
+   // Espículas del limbo: cuántos filamentos alrededor del borde y cuánto brillan (0 = limbo liso)
+   pub spicule_frequency: f32,
+   pub spicule_intensity: f32,
+   // Cuánto se nota cada capa (los valores por defecto son los del diseño original; 0 = la apaga)
+   pub plasma_blend: f32,
+   pub edge_glow_blend: f32,
+}
+
+impl Default for SunParams {
+   fn default() -> Self {
+      SunParams {
+         granulation_strength: 0.15,
+         granulation_flicker_speed: 0.3,
+         spot_color: Color::from_hex(0x994400),
+         umbra_color: Color::from_hex(0x4a1a00),
+         spot_darkness: 0.4,
+         umbra_threshold: 0.6,
+         // El `time * 0.1` original con zoom 3, llevado al zoom de las manchas (20):
+         // sobre la esfera se mueven a la misma velocidad que antes
+         spot_drift_velocity: Vec3::new(0.0, 0.1 * 20.0 / 3.0, 0.0),
+         photosphere_radius: None,
+         spicule_frequency: 40.0,
+         spicule_intensity: 0.0,
+         plasma_blend: 0.15,
+         edge_glow_blend: 0.3,
+      }
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct RockyPlanetParams {
+   // Atmósfera polvorienta tipo Marte: tiñe el disco y aclara el limbo
+   pub dust_color: Color,
+   // 0 = sin atmósfera
+   pub dust_density: f32,
+   // Mezcla el terreno base con un noise "ridged" secundario (None = solo el base)
+   pub terrain_blend: Option<NoiseBlend>,
+   // Nivel del mar sobre el noise del terreno (None = planeta seco)
+   pub sea_level: Option<f32>,
+   // Capas de tormentas de polvo, compuestas en orden (vacío = cielo despejado)
+   pub storm_layers: Vec<DustStormLayer>,
+   pub shallow_water_color: Color,
+   pub deep_water_color: Color,
+   // Ancho de la franja de costa donde se mezclan tierra y agua
+   pub coastline_width: f32,
+   // Tinte extra del reflejo del sol en el agua, sobre el color de la luz (blanco = el de la estrella)
+   pub glint_color: Color,
+   // Casquetes polares: rojizos para Marte, azulados para hielo
+   pub polar_color: Color,
+   // true = borde suavizado con smoothstep, false = borde duro
+   pub soft_polar_edge: bool,
+   // Luz mínima que recibe el material, para que lo oscuro nunca llegue a negro puro
+   pub albedo_floor: f32,
+   // Luces de ciudades en el lado nocturno (0 = planeta deshabitado)
+   pub city_light_brightness: f32,
+   pub city_light_color: Color,
+   // Frecuencia de la cuadrícula de calles en espacio objeto
+   pub city_grid_scale: f32,
+   // Edad geológica de 0 (joven: lisa, con vetas de lava) a 1 (vieja: accidentada y
+   // llena de cráteres); 0.5 = aspecto original
+   pub surface_age: f32,
+   // Cuánto se nota cada capa (los valores por defecto son los del diseño original; 0 = la apaga).
+   // Las nubes son las tormentas de polvo; su sombra va aparte porque solo existe con altitud
+   pub detail_blend: f32,
+   pub cloud_blend: f32,
+   pub cloud_shadow_blend: f32,
+}
+
+impl Default for RockyPlanetParams {
+   fn default() -> Self {
+      RockyPlanetParams {
+         dust_color: Color::from_hex(0xe0a060),
+         dust_density: 0.0,
+         terrain_blend: None,
+         sea_level: None,
+         storm_layers: vec![DustStormLayer::default()],
+         shallow_water_color: Color::from_hex(0x2a8fb0),
+         deep_water_color: Color::from_hex(0x0b2a5a),
+         coastline_width: 0.04,
+         glint_color: Color::from_hex(0xffffff),
+         polar_color: Color::from_hex(0xf0f0f0),
+         soft_polar_edge: true,
+         albedo_floor: 0.0,
+         city_light_brightness: 0.0,
+         city_light_color: Color::from_hex(0xffcc66),
+         city_grid_scale: 60.0,
+         surface_age: 0.5,
+         detail_blend: 0.4,
+         cloud_blend: 0.3,
+         cloud_shadow_blend: 0.4,
+      }
+   }
+}
+
+// Una capa de tormentas de polvo del planeta rocoso
+#[derive(Debug, Clone)]
+pub struct DustStormLayer {
+   pub zoom: f32,
+   // Deriva por unidad de tiempo de superficie
+   pub speed: f32,
+   // Fracción del rango de noise cubierta por tormentas (0.4 = umbral original 0.6)
+   pub coverage: f32,
+   // Altura de la capa: desplaza su sombra según la luz (0 = sin sombra)
+   pub altitude: f32,
+}
+
+impl Default for DustStormLayer {
+   fn default() -> Self {
+      DustStormLayer {
+         zoom: 8.0,
+         speed: 0.1,
+         coverage: 0.4,
+         altitude: 0.0,
+      }
+   }
+}
+
+// Una tormenta del gigante gaseoso, ubicada sobre la esfera
+#[derive(Debug, Clone, PartialEq)]
+pub struct StormSpot {
+   // En radianes: latitud -PI/2..PI/2 (0 = ecuador), longitud alrededor del eje Y
+   pub latitude: f32,
+   pub longitude: f32,
+   // Radio medido sobre la esfera unitaria
+   pub radius: f32,
+   pub color: Color,
+   // Altura en la atmósfera: 0 = nubes altas (claras), 0.5 = color tal cual, 1 = vórtice profundo (oscuro)
+   pub depth: f32,
+}
+
+impl StormSpot {
+   // Centro de la tormenta sobre la esfera unitaria
+   pub fn center(&self) -> Vec3 {
+      Vec3::new(
+         self.latitude.cos() * self.longitude.cos(),
+         self.latitude.sin(),
+         self.latitude.cos() * self.longitude.sin(),
+      )
+   }
+
+   // Color con el que se dibuja según la profundidad
+   pub fn tinted_color(&self) -> Color {
+      let depth = self.depth.clamp(0.0, 1.0);
+      if depth < 0.5 {
+         self.color.lerp(&Color::from_hex(0xfaf6ee), (0.5 - depth) * 2.0 * 0.6)
+      } else {
+         self.color.lerp(&Color::from_hex(0x3a2418), (depth - 0.5) * 2.0 * 0.6)
+      }
+   }
+}
+
+// Reparte `count` tormentas de forma reproducible a partir de la semilla del cuerpo:
+// la misma semilla da siempre el mismo mapa de tormentas
+pub fn generate_storms(seed: i32, count: usize) -> Vec<StormSpot> {
+   let mut rng = StdRng::seed_from_u64(seed as u64);
+   let red = Color::from_hex(0xc74440);
+   let white = Color::from_hex(0xf0e6d8);
+
+   (0..count)
+      .map(|_| StormSpot {
+         // Lejos de los polos, donde las bandas ya no se distinguen
+         latitude: rng.random_range(-1.1..1.1),
+         longitude: rng.random_range(0.0..std::f32::consts::TAU),
+         radius: rng.random_range(0.05..0.2),
+         // Entre óvalos blancos y manchas rojizas
+         color: red.lerp(&white, rng.random_range(0.0..1.0)),
+         depth: rng.random_range(0.0..1.0),
+      })
+      .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct GasGiantParams {
+   // 0 = bandas sinusoidales suaves; valores altos dan bordes nítidos como los cinturones de Júpiter
+   pub band_sharpness: f32,
+   // Sentido en que derivan bandas y tormentas: 1 = prógrado, -1 = retrógrado
+   pub flow_direction: f32,
+   // Vórtice polar: latitud (0..1, en y normalizado) desde la que empieza, cuánto
+   // retuerce las bandas (0 = sin vórtice) y vueltas por unidad de tiempo de superficie
+   pub vortex_latitude: f32,
+   pub vortex_strength: f32,
+   pub vortex_speed: f32,
+   // Radios de la gran mancha: radius_x > radius_y la estira a lo largo de las bandas
+   pub storm_radius_x: f32,
+   pub storm_radius_y: f32,
+   // Colores por los que cicla la gran mancha y ciclos por unidad de tiempo (0 = fija)
+   pub storm_color_gradient: Gradient,
+   pub storm_color_speed: f32,
+   // Velocidad de los remolinos dentro de la mancha (independiente del flujo de las bandas)
+   pub spot_detail_velocity: Vec3,
+   // Tormentas adicionales a la gran mancha (ver generate_storms)
+   pub storms: Vec<StormSpot>,
+   // Luz mínima que recibe el material, para que lo oscuro nunca llegue a negro puro
+   pub albedo_floor: f32,
+   // Cuánto se nota cada capa (los valores por defecto son los del diseño original; 0 = la apaga)
+   pub turbulence_blend: f32,
+   pub storm_blend: f32,
+   pub great_spot_blend: f32,
+   pub detail_blend: f32,
+}
+
+impl Default for GasGiantParams {
+   fn default() -> Self {
+      GasGiantParams {
+         band_sharpness: 0.0,
+         flow_direction: 1.0,
+         vortex_latitude: 0.8,
+         vortex_strength: 0.0,
+         vortex_speed: 0.1,
+         storm_radius_x: 0.3,
+         storm_radius_y: 0.3,
+         storm_color_gradient: Gradient::new(vec![
+            (0.0, Color::from_hex(0xc74440)), // Rojo
+            (0.5, Color::from_hex(0xd9772e)), // Naranja
+            (1.0, Color::from_hex(0x8a4a2a)), // Marrón
+         ]),
+         storm_color_speed: 0.0,
+         spot_detail_velocity: Vec3::new(-0.2, 0.0, 0.0),
+         storms: Vec::new(),
+         albedo_floor: 0.0,
+         turbulence_blend: 0.4,
+         storm_blend: 0.7,
+         great_spot_blend: 0.7,
+         detail_blend: 0.2,
+      }
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct MoonParams {
+   // Densidad relativa de cada escala de cráteres (0 = ninguno, 1 = máximo)
+   pub large_crater_density: f32,
+   pub medium_crater_density: f32,
+   pub small_crater_density: f32,
+   // Cuánto se aclara el borde de cada cráter (0 = sin borde)
+   pub crater_rim_brightness: f32,
+   // 0 = cráteres en cuenco, 1 = fondo plano y oscuro
+   pub crater_floor_flatness: f32,
+   // Fracción de cráteres grandes con pico central y cuánto brilla el pico
+   pub central_peak_probability: f32,
+   pub central_peak_brightness: f32,
+   // Rayos de eyección: activarlos solo en superficies jóvenes
+   pub ejecta_rays: bool,
+   // Alcance de los rayos más allá del borde, en unidades de noise
+   pub ray_length: f32,
+   pub ray_brightness: f32,
+   // Luz de contorno (fresnel) para despegar la luna del fondo; 0 = apagada
+   pub rim_color: Color,
+   pub rim_strength: f32,
+   // Luz mínima que recibe el material, para que lo oscuro nunca llegue a negro puro
+   pub albedo_floor: f32,
+   // Fase fija para previsualizar (0 = nueva, 0.5 = llena, 1 = nueva) con una luz
+   // sintética relativa a la cámara; None = usar la iluminación real de la escena
+   pub phase: Option<f32>,
+   // Rugosidad de Oren-Nayar (0 = Lambert, ~0.5-1 = regolito que no se oscurece en el terminador)
+   pub oren_nayar_roughness: f32,
+   // Cuánto se nota cada capa (los valores por defecto son los del diseño original; 0 = la apaga)
+   pub crater_blend: f32,
+   pub detail_blend: f32,
+}
+
+impl Default for MoonParams {
+   fn default() -> Self {
+      MoonParams {
+         large_crater_density: 0.6,
+         medium_crater_density: 1.0,
+         small_crater_density: 0.8,
+         crater_rim_brightness: 0.3,
+         crater_floor_flatness: 0.0,
+         central_peak_probability: 0.0,
+         central_peak_brightness: 0.6,
+         ejecta_rays: true,
+         ray_length: 0.25,
+         ray_brightness: 0.35,
+         rim_color: Color::from_hex(0xbfd4ff),
+         rim_strength: 0.0,
+         albedo_floor: 0.0,
+         phase: None,
+         oren_nayar_roughness: 0.0,
+         crater_blend: 0.8,
+         detail_blend: 0.15,
+      }
+   }
+}
+
+#[derive(Debug, Clone)]
+pub struct RingParams {
+   // Ondas de densidad en espiral (las que provocan las lunas pastoras); 0 = sin espirales
+   pub spiral_strength: f32,
+   // Vueltas de la espiral por unidad de radio: más alto = brazos más cerrados
+   pub spiral_tightness: f32,
+   // Vueltas completas del patrón por unidad de tiempo
+   pub spiral_speed: f32,
+   // Ancho (en unidades de radio) del desvanecido en los bordes interior y exterior; 0 = corte seco
+   pub edge_width: f32,
+}
+
+impl Default for RingParams {
+   fn default() -> Self {
+      RingParams {
+         spiral_strength: 0.0,
+         spiral_tightness: 20.0,
+         spiral_speed: 0.05,
+         edge_width: 0.0,
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn same_seed_same_storms_and_the_requested_count() {
+      assert_eq!(generate_storms(1337, 6), generate_storms(1337, 6));
+      assert_ne!(generate_storms(1337, 6), generate_storms(42, 6));
+      for count in [0, 1, 12] {
+         assert_eq!(generate_storms(7, count).len(), count);
+      }
+
+      // Repartidas: no todas en la misma latitud ni del mismo tamaño
+      let storms = generate_storms(1337, 12);
+      let spread = |value: fn(&StormSpot) -> f32| {
+         let values: Vec<f32> = storms.iter().map(value).collect();
+         values.iter().cloned().fold(f32::MIN, f32::max) - values.iter().cloned().fold(f32::MAX, f32::min)
+      };
+      assert!(spread(|storm| storm.latitude) > 0.5);
+      assert!(spread(|storm| storm.radius) > 0.03);
+   }
+}
@@ -0,0 +1,160 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::gradient::Gradient;
+use crate::math::{saturate, smoothstep};
+use nalgebra_glm::Vec2;
+
+// Efectos que se aplican sobre la imagen final, después de dibujar todos los cuerpos
+
+// Look retro: reduce cada canal a `levels` valores, sin agregar ruido
+pub fn posterize(framebuffer: &mut Framebuffer, levels: u32) {
+   for pixel in framebuffer.buffer.iter_mut() {
+      *pixel = Color::from_hex(*pixel).posterize(levels).to_hex();
+   }
+}
+
+// Cambio de paleta (infrarrojo, visión alienígena...): cada color se reemplaza
+// por el degradado evaluado en su luminancia
+pub fn palette_map(color: Color, gradient: &Gradient) -> Color {
+   gradient.sample(color.luminance())
+}
+
+pub fn apply_palette(framebuffer: &mut Framebuffer, gradient: &Gradient) {
+   for pixel in framebuffer.buffer.iter_mut() {
+      *pixel = palette_map(Color::from_hex(*pixel), gradient).to_hex();
+   }
+}
+
+// Resplandor del sol sobre toda la imagen (dispersión en la lente/atmósfera).
+// Las posiciones van en coordenadas normalizadas de pantalla: (0, 0) es el centro
+// y ±1 los bordes. Es máximo con el sol centrado y se apaga al salir de pantalla.
+pub fn sun_glare(color: Color, screen_pos: Vec2, sun_screen_pos: Vec2, strength: f32) -> Color {
+   let sun_centered = saturate(1.0 - sun_screen_pos.norm() / 1.5);
+   let near_sun = saturate(1.0 - (screen_pos - sun_screen_pos).norm() / 2.0);
+   let amount = strength * sun_centered * sun_centered * (0.5 + 0.5 * near_sun);
+   color.lerp(&Color::from_hex(0xfff4e0), amount)
+}
+
+pub fn apply_sun_glare(framebuffer: &mut Framebuffer, sun_screen_pos: Vec2, strength: f32) {
+   let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+   for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+      let x = (index % framebuffer.width) as f32 + 0.5;
+      let y = (index / framebuffer.width) as f32 + 0.5;
+      let screen_pos = Vec2::new(x / width * 2.0 - 1.0, y / height * 2.0 - 1.0);
+      *pixel = sun_glare(Color::from_hex(*pixel), screen_pos, sun_screen_pos, strength).to_hex();
+   }
+}
+
+// Espigas de difracción (la estrella de 4 o 6 puntas): aporte a sumar sobre la imagen.
+// Mismas coordenadas normalizadas que sun_glare; la primera espiga apunta hacia +x.
+pub fn flare_spikes(screen_pos: Vec2, sun_screen_pos: Vec2, count: u32, length: f32, brightness: f32) -> Color {
+   let offset = screen_pos - sun_screen_pos;
+   let distance = offset.norm();
+   if count == 0 || length <= 0.0 || distance >= length {
+      return Color::black();
+   }
+
+   // Distancia perpendicular a la espiga más cercana
+   let spike_spacing = 2.0 * std::f32::consts::PI / count as f32;
+   let angle = offset.y.atan2(offset.x);
+   let angle_to_spike = angle - (angle / spike_spacing).round() * spike_spacing;
+   let distance_to_spike = distance * angle_to_spike.sin().abs();
+
+   let spike_width = 0.01;
+   let along = 1.0 - distance / length;
+   let intensity = smoothstep(spike_width, 0.0, distance_to_spike) * along * along * brightness;
+   Color::from_hex(0xfff8ec) * intensity
+}
+
+pub fn apply_flare_spikes(framebuffer: &mut Framebuffer, sun_screen_pos: Vec2, count: u32, length: f32, brightness: f32) {
+   let (width, height) = (framebuffer.width as f32, framebuffer.height as f32);
+   for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+      let x = (index % framebuffer.width) as f32 + 0.5;
+      let y = (index / framebuffer.width) as f32 + 0.5;
+      let screen_pos = Vec2::new(x / width * 2.0 - 1.0, y / height * 2.0 - 1.0);
+      let spikes = flare_spikes(screen_pos, sun_screen_pos, count, length, brightness);
+      *pixel = (Color::from_hex(*pixel) + spikes).to_hex();
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn channels(color: Color) -> [i32; 3] {
+      let hex = color.to_hex();
+      [(hex >> 16 & 0xFF) as i32, (hex >> 8 & 0xFF) as i32, (hex & 0xFF) as i32]
+   }
+
+   fn sample_colors() -> Vec<Color> {
+      let steps = [0u8, 40, 90, 128, 170, 220, 255];
+      let mut colors = Vec::new();
+      for r in steps {
+         for g in steps {
+            for b in steps {
+               colors.push(Color::new(r, g, b));
+            }
+         }
+      }
+      colors
+   }
+
+   #[test]
+   fn black_to_white_palette_is_a_grayscale_pass() {
+      let gray = Gradient::between(Color::black(), Color::new(255, 255, 255));
+      for color in sample_colors() {
+         let [r, g, b] = channels(palette_map(color, &gray));
+         assert!(r == g && g == b, "{color:?} is not gray");
+         assert!((r as f32 / 255.0 - color.luminance()).abs() <= 1.0 / 255.0);
+      }
+   }
+
+   #[test]
+   fn two_color_palette_only_interpolates_its_colors() {
+      let (from, to) = (Color::from_hex(0x102030), Color::from_hex(0xf0c080));
+      let palette = Gradient::between(from, to);
+      let (start, end) = (channels(from), channels(to));
+      for color in sample_colors() {
+         let mapped = channels(palette_map(color, &palette));
+         // Todos los canales avanzan la misma fracción del camino entre los dos colores
+         let t = (mapped[0] - start[0]) as f32 / (end[0] - start[0]) as f32;
+         for channel in 0..3 {
+            let expected = start[channel] as f32 + t * (end[channel] - start[channel]) as f32;
+            assert!((mapped[channel] as f32 - expected).abs() <= 2.0, "{color:?} -> {mapped:?}");
+         }
+      }
+   }
+
+   #[test]
+   fn glare_brightens_more_with_the_sun_near_the_center() {
+      let dark = Color::from_hex(0x101820);
+      let pixels: Vec<Vec2> = (0..25).map(|i| Vec2::new((i % 5) as f32 / 2.0 - 1.0, (i / 5) as f32 / 2.0 - 1.0)).collect();
+      let centered = Vec2::zeros();
+      let near_edge = Vec2::new(0.9, 0.0);
+      let off_screen = Vec2::new(2.0, 0.0);
+
+      for pixel in pixels {
+         let glare = |sun: Vec2| sun_glare(dark, pixel, sun, 0.6).luminance();
+         assert!(glare(centered) > glare(near_edge), "{pixel:?}");
+         assert!(glare(near_edge) > glare(off_screen), "{pixel:?}");
+         assert_eq!(sun_glare(dark, pixel, off_screen, 0.6), dark);
+      }
+      // Con el sol centrado, el centro de la pantalla es lo más lavado
+      assert!(sun_glare(dark, centered, centered, 0.6).luminance() > sun_glare(dark, Vec2::new(1.0, 1.0), centered, 0.6).luminance());
+   }
+
+   #[test]
+   fn four_spikes_shine_along_the_axes_and_not_between() {
+      let sun = Vec2::new(0.2, -0.1);
+      let spike = |direction: Vec2| flare_spikes(sun + direction * 0.3, sun, 4, 0.8, 1.0).luminance();
+
+      let axes = [Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(-1.0, 0.0), Vec2::new(0.0, -1.0)];
+      let diagonals = axes.map(|axis| Vec2::new(axis.x - axis.y, axis.x + axis.y).normalize());
+      for (axis, diagonal) in axes.iter().zip(diagonals) {
+         assert!(spike(*axis) > 0.2, "{axis:?}: {}", spike(*axis));
+         assert_eq!(spike(diagonal), 0.0, "{diagonal:?}");
+      }
+      // Más allá del largo no queda nada
+      assert_eq!(flare_spikes(sun + Vec2::new(0.9, 0.0), sun, 4, 0.8, 1.0), Color::black());
+   }
+}
@@ -0,0 +1,300 @@
+use std::fmt::Write;
+use nalgebra_glm::{Mat4, Vec3};
+use crate::camera::{Camera, create_view_matrix, create_perspective_matrix, create_viewport_matrix};
+use crate::color::Color;
+use crate::emissive::EmissiveAccumulator;
+use crate::framebuffer::Framebuffer;
+use crate::render::{billboard_fragments, rasterize, shade_fragments};
+use crate::fragment::Fragment;
+use crate::shaders::{fragment_shader_hdr, FrameContext, Uniforms};
+use crate::celestial_body::ShaderType;
+use crate::sphere::SphereMesh;
+
+// Rampa de caracteres de menor a mayor luminancia
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+// Un carácter de terminal es aproximadamente el doble de alto que de ancho
+const CHAR_ASPECT: f32 = 2.0;
+
+pub struct PreviewOptions {
+   // Calidad de la esfera (ver SphereMesh::new)
+   pub subdivisions: usize,
+   // Color de los píxeles que no cubre la esfera
+   pub background: Color,
+   // Dibujar un disco con normales falsas en vez de rasterizar la esfera (ver billboard_fragments)
+   pub billboard: bool,
+}
+
+impl Default for PreviewOptions {
+   fn default() -> Self {
+      PreviewOptions {
+         subdivisions: 24,
+         background: Color::black(),
+         billboard: false,
+      }
+   }
+}
+
+// Vista previa en texto de un cuerpo, sin abrir ventana.
+// Las matrices de `uniforms` se ignoran: la esfera se encuadra sola en la grilla;
+// todo lo demás (tiempo, parámetros, luz, hooks) sí se toma de `uniforms`.
+pub fn render_ascii(shader_type: &ShaderType, uniforms: &Uniforms, cols: usize, rows: usize) -> String {
+   ascii_preview(shader_type, uniforms, cols, rows, &PreviewOptions::default(), false)
+}
+
+pub fn render_ascii_with(shader_type: &ShaderType, uniforms: &Uniforms, cols: usize, rows: usize, options: &PreviewOptions) -> String {
+   ascii_preview(shader_type, uniforms, cols, rows, options, false)
+}
+
+// Igual que render_ascii pero coloreando cada carácter con escapes ANSI truecolor
+pub fn render_ascii_truecolor(shader_type: &ShaderType, uniforms: &Uniforms, cols: usize, rows: usize) -> String {
+   ascii_preview(shader_type, uniforms, cols, rows, &PreviewOptions::default(), true)
+}
+
+fn ascii_preview(
+   shader_type: &ShaderType,
+   uniforms: &Uniforms,
+   cols: usize,
+   rows: usize,
+   options: &PreviewOptions,
+   truecolor: bool,
+) -> String {
+   let framebuffer = shade_sphere(shader_type, uniforms, cols, rows, CHAR_ASPECT, options);
+   let mut output = String::with_capacity((cols + 1) * rows);
+
+   for y in 0..rows {
+      for x in 0..cols {
+         let index = y * cols + x;
+         let hex = framebuffer.buffer[index];
+         let luminance = Color::from_hex(hex).luminance();
+         let level = (luminance * (ASCII_RAMP.len() - 1) as f32).round() as usize;
+         let character = ASCII_RAMP[level] as char;
+
+         if truecolor && character != ' ' {
+            let _ = write!(output, "\x1b[38;2;{};{};{}m{}", (hex >> 16) & 0xFF, (hex >> 8) & 0xFF, hex & 0xFF, character);
+         } else {
+            output.push(character);
+         }
+      }
+      if truecolor {
+         output.push_str("\x1b[0m");
+      }
+      output.push('\n');
+   }
+
+   output
+}
+
+// Render en floats lineales sin cuantizar (para componer en otras herramientas).
+// Devuelve width * height colores por filas; el fondo toma options.background.
+// Solo el sol pasa de 1.0 (ver fragment_shader_hdr): lava y luces de ciudades
+// siguen saliendo recortadas como en el render de 8 bits.
+pub fn render_sphere_to_float(shader_type: &ShaderType, uniforms: &Uniforms, width: usize, height: usize) -> Vec<[f32; 3]> {
+   render_sphere_to_float_with(shader_type, uniforms, width, height, &PreviewOptions::default())
+}
+
+pub fn render_sphere_to_float_with(
+   shader_type: &ShaderType,
+   uniforms: &Uniforms,
+   width: usize,
+   height: usize,
+   options: &PreviewOptions,
+) -> Vec<[f32; 3]> {
+   let framing = framing_uniforms(uniforms, width, height, 1.0);
+   let fragments = sphere_fragments(&framing, options);
+   let ctx = FrameContext::new(&framing);
+
+   let background = EmissiveAccumulator::from_color(options.background).linear_rgb();
+   let mut colors = vec![background; width * height];
+   let mut depths = vec![f32::INFINITY; width * height];
+   for fragment in &fragments {
+      let x = fragment.position.x as usize;
+      let y = fragment.position.y as usize;
+      if x < width && y < height {
+         let index = y * width + x;
+         if depths[index] > fragment.depth {
+            colors[index] = fragment_shader_hdr(fragment, &ctx, shader_type);
+            depths[index] = fragment.depth;
+         }
+      }
+   }
+
+   colors
+}
+
+// Rasteriza una esfera unitaria centrada en la imagen y la sombrea con `uniforms`
+fn shade_sphere(
+   shader_type: &ShaderType,
+   uniforms: &Uniforms,
+   width: usize,
+   height: usize,
+   pixel_aspect: f32,
+   options: &PreviewOptions,
+) -> Framebuffer {
+   let framing = framing_uniforms(uniforms, width, height, pixel_aspect);
+   let fragments = sphere_fragments(&framing, options);
+
+   let mut framebuffer = Framebuffer::new(width, height);
+   framebuffer.set_background_color(options.background.to_hex());
+   framebuffer.clear();
+   shade_fragments(&mut framebuffer, &FrameContext::new(&framing), &fragments, shader_type);
+   framebuffer
+}
+
+// Copia de `uniforms` con las matrices del encuadre de la vista previa: la esfera
+// se rasteriza y se sombrea con la misma cámara
+fn framing_uniforms(uniforms: &Uniforms, width: usize, height: usize, pixel_aspect: f32) -> Uniforms {
+   let camera = Camera::new(
+      Vec3::new(0.0, 0.0, 3.0),
+      Vec3::new(0.0, 0.0, 0.0),
+      Vec3::new(0.0, 1.0, 0.0),
+   );
+   Uniforms {
+      model_matrix: Mat4::identity(),
+      view_matrix: create_view_matrix(&camera),
+      projection_matrix: create_perspective_matrix(width as f32, height as f32 * pixel_aspect),
+      viewport_matrix: create_viewport_matrix(width as f32, height as f32),
+      ..uniforms.clone()
+   }
+}
+
+// Fragmentos de la esfera encuadrada en la imagen, todavía sin sombrear
+fn sphere_fragments(framing: &Uniforms, options: &PreviewOptions) -> Vec<Fragment> {
+   if options.billboard {
+      return billboard_fragments(framing);
+   }
+   let sphere = SphereMesh::new(options.subdivisions);
+   rasterize(&sphere.vertices, framing)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::camera::Camera;
+
+   fn test_uniforms() -> Uniforms {
+      Uniforms::new(Mat4::identity(), Mat4::identity(), Mat4::identity(), Mat4::identity(), 0.0)
+   }
+
+   // Posición promedio en la rampa de los caracteres no vacíos
+   fn mean_density(preview: &str) -> f32 {
+      let levels: Vec<usize> = preview
+         .bytes()
+         .filter(|byte| *byte != b' ' && *byte != b'\n')
+         .map(|byte| ASCII_RAMP.iter().position(|ramp| *ramp == byte).unwrap())
+         .collect();
+      levels.iter().sum::<usize>() as f32 / levels.len() as f32
+   }
+
+   #[test]
+   fn ascii_preview_has_the_requested_rows() {
+      let preview = render_ascii(&ShaderType::Moon, &test_uniforms(), 40, 16);
+      assert_eq!(preview.lines().count(), 16);
+      assert!(preview.lines().all(|line| line.len() == 40));
+   }
+
+   #[test]
+   fn bright_sun_uses_denser_characters_than_a_dim_moon() {
+      let mut dim = test_uniforms();
+      dim.albedo_scale = 0.3;
+      let sun = render_ascii(&ShaderType::Sun, &test_uniforms(), 40, 16);
+      let moon = render_ascii(&ShaderType::Moon, &dim, 40, 16);
+      assert!(mean_density(&sun) > mean_density(&moon));
+   }
+
+   #[test]
+   fn float_sun_exceeds_one_where_eight_bits_clamp() {
+      let mut uniforms = test_uniforms();
+      uniforms.emissive_scale = 4.0;
+      let floats = render_sphere_to_float(&ShaderType::Sun, &uniforms, 32, 32);
+      let bytes = shade_sphere(&ShaderType::Sun, &uniforms, 32, 32, 1.0, &PreviewOptions::default());
+
+      let clamped_above_one = floats.iter().zip(&bytes.buffer).any(|(float, hex)| {
+         let channels = [(hex >> 16) & 0xFF, (hex >> 8) & 0xFF, hex & 0xFF];
+         float.iter().zip(channels).any(|(value, byte)| *value > 1.0 && byte == 255)
+      });
+      assert!(clamped_above_one);
+   }
+
+   #[test]
+   fn float_render_ignores_the_caller_camera() {
+      let rim_moon = || {
+         let mut uniforms = test_uniforms();
+         uniforms.params.moon.rim_strength = 0.6;
+         uniforms
+      };
+      let mut elsewhere = rim_moon();
+      let camera = Camera::new(Vec3::new(3.0, 1.0, -2.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+      elsewhere.view_matrix = create_view_matrix(&camera);
+
+      assert_eq!(
+         render_sphere_to_float(&ShaderType::Moon, &elsewhere, 32, 32),
+         render_sphere_to_float(&ShaderType::Moon, &rim_moon(), 32, 32),
+      );
+   }
+
+   #[test]
+   fn preview_ignores_the_caller_camera() {
+      // Efectos que dependen de la dirección de la cámara: reflejo del océano y contorno lunar
+      let view_dependent = || {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.sea_level = Some(0.0);
+         uniforms.params.moon.rim_strength = 0.6;
+         uniforms
+      };
+      let mut elsewhere = view_dependent();
+      let camera = Camera::new(Vec3::new(3.0, 1.0, -2.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+      elsewhere.view_matrix = create_view_matrix(&camera);
+      elsewhere.model_matrix = nalgebra_glm::rotate_y(&Mat4::identity(), 1.2);
+
+      for shader in [ShaderType::Moon, ShaderType::RockyPlanet] {
+         assert_eq!(
+            render_ascii_truecolor(&shader, &elsewhere, 40, 16),
+            render_ascii_truecolor(&shader, &view_dependent(), 40, 16),
+         );
+      }
+   }
+
+   #[test]
+   fn more_subdivisions_give_more_triangles_and_low_counts_stay_round() {
+      let counts: Vec<usize> = [2, 4, 8, 24].iter().map(|n| SphereMesh::new(*n).triangle_count()).collect();
+      assert!(counts.windows(2).all(|pair| pair[0] < pair[1]), "{counts:?}");
+
+      // Fondo magenta para distinguir la esfera también en el lado nocturno
+      let coverage = |subdivisions: usize| {
+         let options = PreviewOptions { subdivisions, background: Color::from_hex(0xff00ff), billboard: false };
+         let framebuffer = shade_sphere(&ShaderType::Moon, &test_uniforms(), 64, 64, 1.0, &options);
+         let covered: Vec<(usize, usize)> = (0..64 * 64)
+            .filter(|index| framebuffer.buffer[*index] != 0xff00ff)
+            .map(|index| (index % 64, index / 64))
+            .collect();
+         let width = covered.iter().map(|p| p.0).max().unwrap() - covered.iter().map(|p| p.0).min().unwrap() + 1;
+         let height = covered.iter().map(|p| p.1).max().unwrap() - covered.iter().map(|p| p.1).min().unwrap() + 1;
+         (covered.len(), width, height)
+      };
+
+      let (smooth, _, _) = coverage(24);
+      let (coarse, width, height) = coverage(6);
+      // Un disco llena pi/4 de su caja; un polígono grueso apenas menos
+      let fill = coarse as f32 / (width * height) as f32;
+      assert!(width.abs_diff(height) <= 1, "{width}x{height}");
+      assert!((0.7..0.82).contains(&fill), "fill {fill}");
+      assert!(coarse as f32 > smooth as f32 * 0.9);
+   }
+
+   #[test]
+   fn corners_take_the_background_color() {
+      let corners = [0, 31, 32 * 31, 32 * 32 - 1];
+      for background in [Color::from_hex(0x0000ff), Color::black()] {
+         let options = PreviewOptions { background, ..PreviewOptions::default() };
+         let framebuffer = shade_sphere(&ShaderType::Moon, &test_uniforms(), 32, 32, 1.0, &options);
+         assert!(corners.iter().all(|corner| framebuffer.buffer[*corner] == background.to_hex()));
+         // El centro es la luna, no el fondo
+         assert_ne!(framebuffer.buffer[16 * 32 + 16], background.to_hex());
+
+         let floats = render_sphere_to_float_with(&ShaderType::Moon, &test_uniforms(), 32, 32, &options);
+         let expected = EmissiveAccumulator::from_color(background).linear_rgb();
+         assert!(corners.iter().all(|corner| floats[*corner] == expected));
+      }
+   }
+}
@@ -0,0 +1,267 @@
+use crate::framebuffer::Framebuffer;
+use crate::fragment::Fragment;
+use crate::vertex::Vertex;
+use crate::triangle::triangle;
+use crate::shaders::{vertex_shader, fragment_shader, FrameContext, FrameRotations, Uniforms};
+use crate::celestial_body::ShaderType;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+// Vertex shader, ensamblado de primitivas y rasterización
+pub fn rasterize(vertex_array: &[Vertex], uniforms: &Uniforms) -> Vec<Fragment> {
+   // Vertex Shader Stage
+   let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
+   for vertex in vertex_array {
+      let transformed = vertex_shader(vertex, uniforms);
+      transformed_vertices.push(transformed);
+   }
+
+   // Primitive Assembly Stage
+   let mut triangles = Vec::new();
+   for i in (0..transformed_vertices.len()).step_by(3) {
+      if i + 2 < transformed_vertices.len() {
+         triangles.push([
+            transformed_vertices[i].clone(),
+            transformed_vertices[i + 1].clone(),
+            transformed_vertices[i + 2].clone(),
+         ]);
+      }
+   }
+
+   // Rasterization Stage
+   let mut fragments = Vec::new();
+   for tri in &triangles {
+      fragments.extend(triangle(&tri[0], &tri[1], &tri[2], &uniforms.light_direction));
+   }
+
+   fragments
+}
+
+pub fn render(
+   framebuffer: &mut Framebuffer,
+   uniforms: &Uniforms,
+   vertex_array: &[Vertex],
+   shader_type: &ShaderType,
+) {
+   let fragments = rasterize(vertex_array, uniforms);
+   let ctx = FrameContext::new(uniforms);
+   shade_fragments(framebuffer, &ctx, &fragments, shader_type);
+}
+
+// Cuerpo lejano dibujado como un disco plano de cara a la cámara (billboard), sin malla.
+// Cada píxel del disco recibe una normal de hemisferio falsa, así la luz lo sigue
+// mostrando redondo. Un fragmento por píxel: mucho menos trabajo que rasterizar la esfera.
+pub fn billboard_fragments(uniforms: &Uniforms) -> Vec<Fragment> {
+   let model = uniforms.model_matrix;
+   let view = uniforms.view_matrix;
+   let to_screen = |world: Vec3| {
+      let clip = uniforms.projection_matrix * view * Vec4::new(world.x, world.y, world.z, 1.0);
+      let screen = uniforms.viewport_matrix * (clip / clip.w);
+      Vec3::new(screen.x, screen.y, screen.z)
+   };
+
+   // Ejes de la cámara en espacio mundo (filas de la view matrix)
+   let right = Vec3::new(view[(0, 0)], view[(0, 1)], view[(0, 2)]).normalize();
+   let up = Vec3::new(view[(1, 0)], view[(1, 1)], view[(1, 2)]).normalize();
+   let back = Vec3::new(view[(2, 0)], view[(2, 1)], view[(2, 2)]).normalize();
+
+   let center_world = Vec3::new(model[(0, 3)], model[(1, 3)], model[(2, 3)]);
+   // Misma parte lineal e inversa que usan los shaders, así la malla y el billboard
+   // llevan las normales a espacio objeto igual
+   let rotations = FrameRotations::new(uniforms);
+   let world_radius = (rotations.body * Vec3::new(uniforms.body_radius, 0.0, 0.0)).norm();
+
+   // En perspectiva el contorno de una esfera se ve algo más grande que su radio
+   // en el plano del centro: r * d / sqrt(d² - r²), con d la distancia a la cámara
+   let eye = view.try_inverse().map(|inverse| Vec3::new(inverse[(0, 3)], inverse[(1, 3)], inverse[(2, 3)]));
+   let silhouette_radius = match eye.map(|eye| (eye - center_world).norm()) {
+      Some(distance) if distance > world_radius => world_radius * distance / (distance * distance - world_radius * world_radius).sqrt(),
+      _ => world_radius,
+   };
+
+   let center = to_screen(center_world);
+   let screen_radius = (to_screen(center_world + right * silhouette_radius) - center).norm();
+   if screen_radius <= 0.0 || !screen_radius.is_finite() {
+      return Vec::new();
+   }
+
+   // La viewport matrix lleva el centro de la pantalla en su traslación: de ahí el tamaño
+   let width = (uniforms.viewport_matrix[(0, 3)] * 2.0).max(0.0);
+   let height = (uniforms.viewport_matrix[(1, 3)] * 2.0).max(0.0);
+   let min_x = (center.x - screen_radius).floor().max(0.0) as usize;
+   let min_y = (center.y - screen_radius).floor().max(0.0) as usize;
+   let max_x = (center.x + screen_radius).ceil().min(width) as usize;
+   let max_y = (center.y + screen_radius).ceil().min(height) as usize;
+
+   let mut fragments = Vec::new();
+   for y in min_y..max_y {
+      for x in min_x..max_x {
+         let offset_x = (x as f32 + 0.5 - center.x) / screen_radius;
+         let offset_y = (y as f32 + 0.5 - center.y) / screen_radius;
+         let offset_squared = offset_x * offset_x + offset_y * offset_y;
+         if offset_squared > 1.0 {
+            continue;
+         }
+
+         // Hemisferio hacia la cámara; la Y de pantalla crece hacia abajo
+         let bulge = (1.0 - offset_squared).sqrt();
+         let normal = (right * offset_x - up * offset_y + back * bulge).normalize();
+         let object_normal = (rotations.body_inverse * normal).normalize();
+
+         fragments.push(
+            Fragment::default()
+               .with_position(x as f32, y as f32)
+               .with_depth(center.z)
+               .with_normal(normal)
+               .with_vertex_position(object_normal * uniforms.body_radius)
+               .with_world_position(center_world + normal * world_radius)
+               .with_intensity(normal.dot(&uniforms.light_direction).max(0.0))
+         );
+      }
+   }
+
+   fragments
+}
+
+pub fn render_billboard(framebuffer: &mut Framebuffer, uniforms: &Uniforms, shader_type: &ShaderType) {
+   let fragments = billboard_fragments(uniforms);
+   let ctx = FrameContext::new(uniforms);
+   shade_fragments(framebuffer, &ctx, &fragments, shader_type);
+}
+
+// Fragment Processing Stage
+pub fn shade_fragments(
+   framebuffer: &mut Framebuffer,
+   ctx: &FrameContext,
+   fragments: &[Fragment],
+   shader_type: &ShaderType,
+) {
+   for fragment in fragments {
+      let x = fragment.position.x as usize;
+      let y = fragment.position.y as usize;
+
+      if x < framebuffer.width && y < framebuffer.height {
+         // Apply fragment shader
+         let shaded_color = fragment_shader(fragment, ctx, shader_type);
+         framebuffer.put_pixel(x, y, fragment.depth, shaded_color);
+      }
+   }
+}
+
+pub fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
+   let (sin_x, cos_x) = rotation.x.sin_cos();
+   let (sin_y, cos_y) = rotation.y.sin_cos();
+   let (sin_z, cos_z) = rotation.z.sin_cos();
+
+   let rotation_matrix_x = Mat4::new(
+      1.0,  0.0,    0.0,   0.0,
+      0.0,  cos_x, -sin_x, 0.0,
+      0.0,  sin_x,  cos_x, 0.0,
+      0.0,  0.0,    0.0,   1.0,
+   );
+
+   let rotation_matrix_y = Mat4::new(
+      cos_y,  0.0,  sin_y, 0.0,
+      0.0,    1.0,  0.0,   0.0,
+      -sin_y, 0.0,  cos_y, 0.0,
+      0.0,    0.0,  0.0,   1.0,
+   );
+
+   let rotation_matrix_z = Mat4::new(
+      cos_z, -sin_z, 0.0, 0.0,
+      sin_z,  cos_z, 0.0, 0.0,
+      0.0,    0.0,   1.0, 0.0,
+      0.0,    0.0,   0.0, 1.0,
+   );
+
+   let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
+
+   let transform_matrix = Mat4::new(
+      scale, 0.0,   0.0,   translation.x,
+      0.0,   scale, 0.0,   translation.y,
+      0.0,   0.0,   scale, translation.z,
+      0.0,   0.0,   0.0,   1.0,
+   );
+
+   transform_matrix * rotation_matrix
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::camera::{create_perspective_matrix, create_view_matrix, create_viewport_matrix, Camera};
+   use crate::sphere::SphereMesh;
+
+   const SIZE: usize = 64;
+
+   // Esfera unitaria en el origen vista desde +z, iluminada desde `light_direction`
+   fn framed_uniforms(light_direction: Vec3) -> Uniforms {
+      let camera = Camera::new(Vec3::new(0.0, 0.0, 4.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+      let mut uniforms = Uniforms::new(
+         Mat4::identity(),
+         create_view_matrix(&camera),
+         create_perspective_matrix(SIZE as f32, SIZE as f32),
+         create_viewport_matrix(SIZE as f32, SIZE as f32),
+         0.0,
+      );
+      uniforms.light_direction = light_direction.normalize();
+      uniforms
+   }
+
+   #[test]
+   fn billboard_moon_is_lit_on_the_light_side_with_fewer_fragments() {
+      let uniforms = framed_uniforms(Vec3::new(1.0, 0.0, 0.3));
+      let billboard = billboard_fragments(&uniforms);
+      let mesh = rasterize(&SphereMesh::new(24).vertices, &uniforms);
+      assert!(!billboard.is_empty());
+      // La malla rasteriza los dos hemisferios (no hay descarte de caras traseras) y solapa bordes
+      assert!(billboard.len() * 3 < mesh.len() * 2, "billboard {} vs mesh {}", billboard.len(), mesh.len());
+
+      let mut flat = Framebuffer::new(SIZE, SIZE);
+      render_billboard(&mut flat, &uniforms, &ShaderType::Moon);
+      let mut sphere = Framebuffer::new(SIZE, SIZE);
+      render(&mut sphere, &uniforms, &SphereMesh::new(24).vertices, &ShaderType::Moon);
+      let luminance = |framebuffer: &Framebuffer, x: usize, y: usize| {
+         crate::color::Color::from_hex(framebuffer.buffer[y * SIZE + x]).luminance()
+      };
+
+      // La luz llega desde +x: el lado derecho del disco brilla y el izquierdo queda en
+      // la luz ambiente, igual que en la esfera rasterizada
+      let (center, offset) = (SIZE / 2, SIZE / 8);
+      let (lit, dark) = (luminance(&flat, center + offset, center), luminance(&flat, center - offset, center));
+      assert!(lit > dark * 1.5, "lit {lit}, dark {dark}");
+      assert!((lit - luminance(&sphere, center + offset, center)).abs() < 0.1);
+      assert!((dark - luminance(&sphere, center - offset, center)).abs() < 0.1);
+   }
+
+   #[test]
+   fn world_position_adds_the_model_translation() {
+      let mesh = SphereMesh::new(8);
+      let identity = framed_uniforms(Vec3::new(0.0, 0.0, 1.0));
+      let fragments = rasterize(&mesh.vertices, &identity);
+      assert!(!fragments.is_empty());
+      assert!(fragments.iter().all(|fragment| (fragment.world_position - fragment.vertex_position).norm() < 1e-5));
+
+      let translation = Vec3::new(0.3, -0.2, 0.5);
+      let mut moved = framed_uniforms(Vec3::new(0.0, 0.0, 1.0));
+      moved.model_matrix = create_model_matrix(translation, 1.0, Vec3::zeros());
+      let fragments = rasterize(&mesh.vertices, &moved);
+      assert!(!fragments.is_empty());
+      assert!(fragments.iter().all(|fragment| (fragment.world_position - fragment.vertex_position - translation).norm() < 1e-4));
+   }
+
+   #[test]
+   fn billboard_maps_normals_to_object_space_like_the_shaders() {
+      // Aplastado en Y: la model matrix no tiene inversa y los shaders usan la identidad
+      let mut uniforms = framed_uniforms(Vec3::new(0.0, 0.0, 1.0));
+      uniforms.model_matrix = Mat4::new_nonuniform_scaling(&Vec3::new(1.0, 0.0, 1.0));
+      let rotations = FrameRotations::new(&uniforms);
+      assert_eq!(rotations.body_inverse, nalgebra_glm::Mat3::identity());
+
+      let fragments = billboard_fragments(&uniforms);
+      assert!(!fragments.is_empty());
+      for fragment in &fragments {
+         let expected = (rotations.body_inverse * fragment.normal).normalize() * uniforms.body_radius;
+         assert!((fragment.vertex_position - expected).norm() < 1e-5, "{:?}", fragment.normal);
+      }
+   }
+}
@@ -0,0 +1,139 @@
+use nalgebra_glm::Vec3;
+use crate::camera::{Camera, create_view_matrix, create_perspective_matrix, create_viewport_matrix};
+use crate::celestial_body::{CelestialBody, ShaderType};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::params::ShaderParams;
+use crate::render::{render, render_billboard, create_model_matrix};
+use crate::shaders::{Shader, Uniforms};
+use std::sync::Arc;
+use crate::sphere::SphereMesh;
+
+// Un cuerpo listo para dibujar: qué shader usa, dónde está y con qué parámetros
+pub struct BodyInstance {
+   pub shader_type: ShaderType,
+   pub position: Vec3,
+   pub scale: f32,
+   pub rotation: Vec3,
+   pub seed: i32,
+   // Ver Uniforms::rotation_period
+   pub rotation_period: f32,
+   // Ver Uniforms::emissive_scale
+   pub emissive_scale: f32,
+   // Cuerpo lejano: disco plano con normales falsas en vez de la esfera completa
+   pub billboard: bool,
+   pub params: ShaderParams,
+}
+
+impl BodyInstance {
+   pub fn new(shader_type: ShaderType, position: Vec3, scale: f32) -> Self {
+      BodyInstance {
+         shader_type,
+         position,
+         scale,
+         rotation: Vec3::new(0.0, 0.0, 0.0),
+         seed: 1337,
+         rotation_period: 1.0,
+         emissive_scale: 1.0,
+         billboard: false,
+         params: ShaderParams::default(),
+      }
+   }
+
+   pub fn with_rotation(mut self, rotation: Vec3) -> Self {
+      self.rotation = rotation;
+      self
+   }
+
+   pub fn with_seed(mut self, seed: i32) -> Self {
+      self.seed = seed;
+      self
+   }
+
+   pub fn with_rotation_period(mut self, rotation_period: f32) -> Self {
+      self.rotation_period = rotation_period;
+      self
+   }
+
+   pub fn with_emissive_scale(mut self, emissive_scale: f32) -> Self {
+      self.emissive_scale = emissive_scale;
+      self
+   }
+
+   pub fn with_billboard(mut self, billboard: bool) -> Self {
+      self.billboard = billboard;
+      self
+   }
+
+   pub fn with_params(mut self, params: ShaderParams) -> Self {
+      self.params = params;
+      self
+   }
+}
+
+impl From<&CelestialBody> for BodyInstance {
+   fn from(body: &CelestialBody) -> Self {
+      BodyInstance::new(body.shader_type, body.position, body.scale)
+         .with_rotation(body.rotation)
+   }
+}
+
+pub struct Scene {
+   pub bodies: Vec<BodyInstance>,
+   // Calidad de la esfera con la que se dibujan todos los cuerpos (ver SphereMesh::new)
+   pub subdivisions: usize,
+   // Color de los píxeles donde no hay ningún cuerpo
+   pub background: Color,
+   // Color de la luz de la estrella, igual para todos los cuerpos (ver Uniforms::light_color)
+   pub light_color: Color,
+   // Shaders propios para los cuerpos con ShaderType::Custom (ver Uniforms::custom_shaders)
+   pub custom_shaders: Vec<Arc<dyn Shader>>,
+}
+
+impl Default for Scene {
+   fn default() -> Self {
+      Scene {
+         bodies: Vec::new(),
+         subdivisions: 24,
+         background: Color::black(),
+         light_color: Color::new(255, 255, 255),
+         custom_shaders: Vec::new(),
+      }
+   }
+}
+
+// Rasteriza y sombrea todos los cuerpos de la escena en un solo framebuffer,
+// respetando la profundidad entre ellos
+pub fn shade_scene(scene: &Scene, camera: &Camera, time: f32, width: usize, height: usize) -> Framebuffer {
+   let view_matrix = create_view_matrix(camera);
+   let projection_matrix = create_perspective_matrix(width as f32, height as f32);
+   let viewport_matrix = create_viewport_matrix(width as f32, height as f32);
+   let sphere = SphereMesh::new(scene.subdivisions);
+
+   let mut framebuffer = Framebuffer::new(width, height);
+   framebuffer.set_background_color(scene.background.to_hex());
+   framebuffer.clear();
+   for body in &scene.bodies {
+      let mut uniforms = Uniforms::new(
+         create_model_matrix(body.position, body.scale, body.rotation),
+         view_matrix,
+         projection_matrix,
+         viewport_matrix,
+         time,
+      );
+      uniforms.seed = body.seed;
+      uniforms.rotation_period = body.rotation_period;
+      uniforms.emissive_scale = body.emissive_scale;
+      uniforms.light_color = scene.light_color;
+      uniforms.custom_shaders = scene.custom_shaders.clone();
+      uniforms.update_params(body.params.clone());
+
+      if body.billboard {
+         render_billboard(&mut framebuffer, &uniforms, &body.shader_type);
+      } else {
+         render(&mut framebuffer, &uniforms, &sphere.vertices, &body.shader_type);
+      }
+   }
+
+   framebuffer
+}
@@ -2,16 +2,89 @@ use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use crate::color::Color;
 use crate::celestial_body::ShaderType;
-use fastnoise_lite::{FastNoiseLite, NoiseType};
+use crate::params::{DustStormLayer, MoonParams, ShaderParams};
+use crate::emissive::EmissiveAccumulator;
+use crate::sphere::sphere_uv;
+use crate::math::{blend_noise, remap, saturate, smoothstep};
+use fastnoise_lite::{CellularReturnType, FastNoiseLite, NoiseType};
+use std::sync::Arc;
+
+// Máscara de exploración: recibe la posición en espacio objeto y devuelve
+// 0.0 (sin descubrir, negro) a 1.0 (descubierto)
+pub type RevealMask = Arc<dyn Fn(nalgebra_glm::Vec3) -> f32 + Send + Sync>;
+
+// Entorno que reflejan las superficies brillantes (hielo, océanos): recibe la
+// dirección reflejada en espacio mundo y devuelve el color visto en esa dirección
+pub type EnvironmentMap = Arc<dyn Fn(nalgebra_glm::Vec3) -> Color + Send + Sync>;
+
+// Tipo de suelo que dicta un mapa externo para el planeta rocoso
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Biome {
+   Ocean,
+   Desert,
+   Rock,
+   Forest,
+   Ice,
+}
+
+// Datos de terreno externos (por ejemplo, mapas de un juego): recibe la posición en
+// espacio objeto y devuelve la altura (-1..1, con el mismo rango que el noise) y el bioma
+pub type TerrainProvider = Arc<dyn Fn(nalgebra_glm::Vec3) -> (f32, Biome) + Send + Sync>;
+
+// Shader definido fuera del crate: se registra en `Uniforms::custom_shaders` y se
+// elige con `ShaderType::Custom(índice)`, sin tocar el enum
+pub trait Shader: Send + Sync {
+   fn shade(&self, fragment: &Fragment, ctx: &FrameContext, light: &LightingResult) -> Color;
+}
+
+// Cualquier closure con la misma firma sirve como shader
+impl<F> Shader for F
+where
+   F: Fn(&Fragment, &FrameContext, &LightingResult) -> Color + Send + Sync,
+{
+   fn shade(&self, fragment: &Fragment, ctx: &FrameContext, light: &LightingResult) -> Color {
+      self(fragment, ctx, light)
+   }
+}
 
 // Estructura de Uniforms actualizada
+#[derive(Clone)]
 pub struct Uniforms {
    pub model_matrix: nalgebra_glm::Mat4,
    pub view_matrix: nalgebra_glm::Mat4,
    pub projection_matrix: nalgebra_glm::Mat4,
    pub viewport_matrix: nalgebra_glm::Mat4,
    pub time: f32,
-   pub noise: FastNoiseLite,
+   // Multiplicador global de la animación: 0 congela, 2 duplica la velocidad
+   pub time_scale: f32,
+   // Duración del día del cuerpo: con la mitad, nubes y tormentas van el doble de rápido
+   pub rotation_period: f32,
+   // Dirección hacia la luz, la misma que usa el rasterizador para `intensity`
+   pub light_direction: nalgebra_glm::Vec3,
+   // Color de esa luz: tiñe solo el término difuso (blanco = sin teñir)
+   pub light_color: Color,
+   // Semilla del noise: cambia la superficie sin cambiar el estilo del cuerpo
+   pub seed: i32,
+   // None = cuerpo completamente descubierto
+   pub reveal_mask: Option<RevealMask>,
+   // None = sin reflejos del entorno
+   pub environment: Option<EnvironmentMap>,
+   // None = continentes y océanos procedurales; si está, reemplaza la clasificación del noise
+   pub terrain_provider: Option<TerrainProvider>,
+   // Si la malla no trae UVs (todas en cero), derivarlas de la posición sobre la esfera
+   pub sphere_uv_fallback: bool,
+   // Radio del cuerpo en unidades de la malla: las posiciones se dividen por él
+   // antes de muestrear el noise, así los detalles tienen el mismo tamaño relativo
+   pub body_radius: f32,
+   // Brillo general del material (antes de la luz): 0.5 = la mitad de reflectante
+   pub albedo_scale: f32,
+   // Multiplicador de todo lo que emite luz propia (estrella, luces de ciudades): 0 = apagado
+   pub emissive_scale: f32,
+   // Calidad: 0 = sin capas de detalle, 1 = sin las más finas, 2 = completo
+   pub detail_level: u8,
+   // Shaders propios; `ShaderType::Custom(i)` usa el i-ésimo
+   pub custom_shaders: Vec<Arc<dyn Shader>>,
+   pub params: ShaderParams,
 }
 
 impl Uniforms {
@@ -22,16 +95,180 @@ impl Uniforms {
       viewport_matrix: nalgebra_glm::Mat4,
       time: f32,
    ) -> Self {
-      let mut noise = FastNoiseLite::new();
-      noise.set_noise_type(Some(NoiseType::OpenSimplex2));
-      
       Uniforms {
          model_matrix,
          view_matrix,
          projection_matrix,
          viewport_matrix,
          time,
+         time_scale: 1.0,
+         rotation_period: 1.0,
+         light_direction: nalgebra_glm::Vec3::new(0.0, 0.0, 1.0),
+         light_color: Color::new(255, 255, 255),
+         seed: 1337,
+         reveal_mask: None,
+         environment: None,
+         terrain_provider: None,
+         sphere_uv_fallback: false,
+         body_radius: 1.0,
+         albedo_scale: 1.0,
+         emissive_scale: 1.0,
+         detail_level: 2,
+         custom_shaders: Vec::new(),
+         params: ShaderParams::default(),
+      }
+   }
+
+   // Reemplaza los parámetros de los shaders sin tocar matrices ni tiempo.
+   // Los shaders leen `params` en cada fragmento, así que el cambio se ve en el siguiente render.
+   pub fn update_params(&mut self, params: ShaderParams) {
+      self.params = params;
+   }
+
+   // Tiempo que deben usar los shaders para animar
+   pub fn effective_time(&self) -> f32 {
+      self.time * self.time_scale
+   }
+
+   // Tiempo para la animación de la superficie (nubes, tormentas, turbulencias).
+   // Un período de 0 o negativo se toma como el día más corto permitido, sin dividir por cero
+   pub fn surface_time(&self) -> f32 {
+      self.effective_time() / self.rotation_period.max(0.01)
+   }
+
+   // Dirección de la luz llevada al espacio objeto del cuerpo (deshace la model matrix)
+   pub fn object_light_direction(&self) -> nalgebra_glm::Vec3 {
+      nalgebra_glm::mat4_to_mat3(&self.model_matrix)
+         .try_inverse()
+         .map(|inverse| inverse * self.light_direction)
+         .unwrap_or(self.light_direction)
+         .normalize()
+   }
+
+   // Dirección hacia la cámara en espacio mundo (eje Z de la vista)
+   pub fn view_direction(&self) -> nalgebra_glm::Vec3 {
+      nalgebra_glm::Vec3::new(
+         self.view_matrix[(2, 0)],
+         self.view_matrix[(2, 1)],
+         self.view_matrix[(2, 2)],
+      ).normalize()
+   }
+}
+
+// Rotaciones que dependen solo de los uniforms y del tiempo: se arman una vez por
+// frame y los shaders solo multiplican, sin trigonometría por fragmento
+pub struct FrameRotations {
+   // Parte lineal de la model matrix (giro y escala del cuerpo) y su inversa
+   pub body: nalgebra_glm::Mat3,
+   pub body_inverse: nalgebra_glm::Mat3,
+   // Giro del vórtice polar del gigante gaseoso alrededor de +Y (polo norte);
+   // el polo sur gira al revés, con la transpuesta
+   pub polar_vortex: nalgebra_glm::Mat3,
+}
+
+impl FrameRotations {
+   pub fn new(uniforms: &Uniforms) -> Self {
+      let body = nalgebra_glm::mat4_to_mat3(&uniforms.model_matrix);
+      let gas_giant = &uniforms.params.gas_giant;
+      let vortex_turns = gas_giant.vortex_strength + uniforms.surface_time() * gas_giant.vortex_speed;
+      
+      FrameRotations {
+         body,
+         body_inverse: body.try_inverse().unwrap_or(nalgebra_glm::Mat3::identity()),
+         polar_vortex: rotation_y(vortex_turns * 2.0 * std::f32::consts::PI),
+      }
+   }
+}
+
+fn rotation_y(angle: f32) -> nalgebra_glm::Mat3 {
+   let (sin, cos) = angle.sin_cos();
+   nalgebra_glm::Mat3::new(
+      cos,  0.0, -sin,
+      0.0,  1.0,  0.0,
+      sin,  0.0,  cos,
+   )
+}
+
+// Todo lo que es constante durante un frame y es caro de construir (generadores
+// de noise, valores derivados de los uniforms) se crea una sola vez aquí y los
+// shaders lo reciben ya hecho, en vez de reconstruirlo por fragmento.
+pub struct FrameContext<'a> {
+   pub uniforms: &'a Uniforms,
+   pub noise: FastNoiseLite,
+   // Noise celular: devuelve un valor constante dentro de cada celda (sirve como id)
+   pub cell_noise: FastNoiseLite,
+   // uniforms.effective_time()
+   pub time: f32,
+   // uniforms.surface_time()
+   pub surface_time: f32,
+   // uniforms.view_direction()
+   pub view_direction: nalgebra_glm::Vec3,
+   // uniforms.object_light_direction()
+   pub object_light_direction: nalgebra_glm::Vec3,
+   pub rotations: FrameRotations,
+   // Centros de las tormentas del gigante gaseoso sobre la esfera unitaria (StormSpot::center)
+   pub storm_centers: Vec<nalgebra_glm::Vec3>,
+   // Color de la gran mancha en este frame: va y vuelve por el degradado y con
+   // velocidad 0 queda en el primer color
+   pub great_spot_color: Color,
+}
+
+impl<'a> FrameContext<'a> {
+   pub fn new(uniforms: &'a Uniforms) -> Self {
+      let mut noise = FastNoiseLite::new();
+      noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+      noise.set_seed(Some(uniforms.seed));
+      
+      let mut cell_noise = FastNoiseLite::new();
+      cell_noise.set_seed(Some(uniforms.seed));
+      cell_noise.set_noise_type(Some(NoiseType::Cellular));
+      cell_noise.set_cellular_return_type(Some(CellularReturnType::CellValue));
+      cell_noise.set_frequency(Some(1.0));
+      
+      let rotations = FrameRotations::new(uniforms);
+      let gas_giant = &uniforms.params.gas_giant;
+      let spot_cycle = 0.5 - 0.5 * (uniforms.effective_time() * gas_giant.storm_color_speed * 2.0 * std::f32::consts::PI).cos();
+      
+      FrameContext {
+         uniforms,
          noise,
+         cell_noise,
+         time: uniforms.effective_time(),
+         surface_time: uniforms.surface_time(),
+         view_direction: uniforms.view_direction(),
+         object_light_direction: (rotations.body_inverse * uniforms.light_direction).normalize(),
+         rotations,
+         storm_centers: gas_giant.storms.iter().map(|storm| storm.center()).collect(),
+         great_spot_color: gas_giant.storm_color_gradient.sample(spot_cycle),
+      }
+   }
+   
+   // Posición en espacio objeto normalizada al radio del cuerpo, la que usan los shaders para el noise
+   pub fn sample_position(&self, fragment: &Fragment) -> nalgebra_glm::Vec3 {
+      fragment.vertex_position / self.uniforms.body_radius
+   }
+}
+
+// Iluminación ya calculada por fuera (p.ej. un pase de sombras propio).
+// Los shaders la usan en vez de `fragment.intensity`:
+//  - diffuse: término N·L (0..1), lo que antes era `intensity`
+//  - specular: escala de los brillos especulares (0 = sin brillos, 1 = normal)
+//  - ambient: escala de la luz ambiente propia de cada material (1 = normal)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightingResult {
+   pub diffuse: f32,
+   pub specular: f32,
+   pub ambient: f32,
+}
+
+impl LightingResult {
+   // La iluminación que usa `fragment_shader`: la intensidad del rasterizador
+   // como difusa y ambiente/especular sin modificar
+   pub fn from_intensity(intensity: f32) -> Self {
+      LightingResult {
+         diffuse: intensity,
+         specular: 1.0,
+         ambient: 1.0,
       }
    }
 }
@@ -45,10 +282,10 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
       1.0
    );
 
+   let world = uniforms.model_matrix * position;
    let transformed = uniforms.projection_matrix 
       * uniforms.view_matrix 
-      * uniforms.model_matrix 
-      * position;
+      * world;
 
    let w = transformed.w;
    let ndc_position = nalgebra_glm::Vec4::new(
@@ -60,18 +297,18 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
 
    let screen_position = uniforms.viewport_matrix * ndc_position;
 
-   let model_mat3 = nalgebra_glm::Mat3::new(
-      uniforms.model_matrix[0], uniforms.model_matrix[1], uniforms.model_matrix[2],
-      uniforms.model_matrix[4], uniforms.model_matrix[5], uniforms.model_matrix[6],
-      uniforms.model_matrix[8], uniforms.model_matrix[9], uniforms.model_matrix[10]
-   );
+   let model_mat3 = nalgebra_glm::mat4_to_mat3(&uniforms.model_matrix);
    
    let normal_matrix = model_mat3.transpose().try_inverse().unwrap_or(nalgebra_glm::Mat3::identity());
    let transformed_normal = normal_matrix * vertex.normal;
+   // La tangente está sobre la superficie: se transforma con la model matrix, lo que
+   // la mantiene perpendicular a la normal transformada incluso con escala no uniforme
+   let transformed_tangent = model_mat3 * vertex.tangent;
 
    Vertex {
       position: vertex.position,
       normal: vertex.normal,
+      tangent: vertex.tangent,
       tex_coords: vertex.tex_coords,
       color: vertex.color,
       transformed_position: nalgebra_glm::Vec3::new(
@@ -80,18 +317,64 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
          screen_position.z
       ),
       transformed_normal,
+      transformed_tangent,
+      world_position: nalgebra_glm::Vec3::new(world.x, world.y, world.z),
    }
 }
 
 // Fragment shader dispatcher
-pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms, shader_type: &ShaderType) -> Color {
+pub fn fragment_shader(fragment: &Fragment, ctx: &FrameContext, shader_type: &ShaderType) -> Color {
+   fragment_shader_lit(fragment, ctx, shader_type, LightingResult::from_intensity(fragment.intensity))
+}
+
+// Igual que `fragment_shader` pero con la iluminación dada por el llamador
+pub fn fragment_shader_lit(fragment: &Fragment, ctx: &FrameContext, shader_type: &ShaderType, light: LightingResult) -> Color {
+   match shader_type {
+      ShaderType::Sun => sun_shader(fragment, ctx),
+      ShaderType::RockyPlanet => reveal(rocky_planet_shader(fragment, ctx, &light), fragment, ctx),
+      ShaderType::GasGiant => reveal(gas_giant_shader(fragment, ctx, &light), fragment, ctx),
+      ShaderType::Moon => reveal(moon_shader(fragment, ctx, &light), fragment, ctx),
+      ShaderType::RingedPlanet => rings_shader(fragment, ctx),
+      ShaderType::Starfield => starfield_shader(fragment, ctx),
+      ShaderType::Custom(index) => match ctx.uniforms.custom_shaders.get(*index) {
+         Some(shader) => shader.shade(fragment, ctx, &light),
+         None => Color::black(),
+      },
+   }
+}
+
+// Mundos híbridos: mezcla dos shaders según una máscara espacial por fragmento
+// (latitud, noise, hemisferio...). 0 = solo `a`, 1 = solo `b`.
+pub fn fragment_shader_masked(fragment: &Fragment, ctx: &FrameContext, a: &ShaderType, b: &ShaderType, mask: f32) -> Color {
+   let mask = saturate(mask);
+   if mask <= 0.0 {
+      return fragment_shader(fragment, ctx, a);
+   }
+   if mask >= 1.0 {
+      return fragment_shader(fragment, ctx, b);
+   }
+   
+   let color_a = fragment_shader(fragment, ctx, a);
+   let color_b = fragment_shader(fragment, ctx, b);
+   lerp_color(&color_a, &color_b, mask)
+}
+
+// Variante HDR: colores lineales en floats, antes de cuantizar a 8 bits.
+// Solo el sol emite por encima de 1.0; el resto de materiales devuelve su color normal.
+pub fn fragment_shader_hdr(fragment: &Fragment, ctx: &FrameContext, shader_type: &ShaderType) -> [f32; 3] {
    match shader_type {
-      ShaderType::Sun => sun_shader(fragment, uniforms),
-      ShaderType::RockyPlanet => rocky_planet_shader(fragment, uniforms),
-      ShaderType::GasGiant => gas_giant_shader(fragment, uniforms),
-      ShaderType::Moon => moon_shader(fragment, uniforms),
-      ShaderType::RingedPlanet => rings_shader(fragment, uniforms),
-      ShaderType::Starfield => starfield_shader(fragment, uniforms),
+      ShaderType::Sun => sun_emission(fragment, ctx).linear_rgb(),
+      _ => EmissiveAccumulator::from_color(fragment_shader(fragment, ctx, shader_type)).linear_rgb(),
+   }
+}
+
+// Coordenadas de textura para shaders que muestrean imágenes; con
+// `sphere_uv_fallback` las mallas sin UVs se texturizan como esferas
+pub fn fragment_uv(fragment: &Fragment, ctx: &FrameContext) -> nalgebra_glm::Vec2 {
+   if ctx.uniforms.sphere_uv_fallback && fragment.tex_coords.norm_squared() == 0.0 {
+      sphere_uv(fragment.vertex_position)
+   } else {
+      fragment.tex_coords
    }
 }
 
@@ -137,12 +420,93 @@ fn blend_colors(base: &Color, overlay: &Color, factor: f32) -> Color {
    )
 }
 
+// Ajustes comunes al color de superficie de los cuerpos iluminados, antes de la luz
+fn surface_albedo(albedo: Color, fragment: &Fragment, ctx: &FrameContext) -> Color {
+   albedo * fragment.ao.clamp(0.0, 1.0) * ctx.uniforms.albedo_scale.max(0.0)
+}
+
+// Máscara de descubrimiento sobre el color ya iluminado, para que ni los brillos
+// especulares ni las luces nocturnas ni la atmósfera asomen en zonas ocultas
+fn reveal(color: Color, fragment: &Fragment, ctx: &FrameContext) -> Color {
+   match &ctx.uniforms.reveal_mask {
+      Some(mask) => color * mask(fragment.vertex_position).clamp(0.0, 1.0),
+      None => color,
+   }
+}
+
+// Ilumina un albedo: la parte difusa se tiñe con el color de la luz y la ambiente
+// no; `floor` es la intensidad mínima del material, igual que antes
+fn apply_light(albedo: Color, diffuse: f32, ambient: f32, floor: f32, ctx: &FrameContext) -> Color {
+   let intensity = (diffuse + ambient).max(floor);
+   let diffuse_share = if intensity > 0.0 { saturate(diffuse / intensity) } else { 0.0 };
+   let tint = Color::new(255, 255, 255).lerp(&ctx.uniforms.light_color, diffuse_share);
+   albedo.modulate(&tint) * intensity
+}
+
+// Término especular de Blinn-Phong
+fn specular(normal: &nalgebra_glm::Vec3, light_dir: &nalgebra_glm::Vec3, view_dir: &nalgebra_glm::Vec3, shininess: f32) -> f32 {
+   let half_dir = (light_dir + view_dir).normalize();
+   normal.dot(&half_dir).max(0.0).powf(shininess)
+}
+
+// Corrección de Oren-Nayar sobre el término de Lambert: las superficies rugosas
+// (regolito) se ven más planas y conservan brillo cerca del terminador.
+// Con rugosidad 0 vale exactamente 1 (Lambert puro).
+fn oren_nayar_factor(normal: &nalgebra_glm::Vec3, light_dir: &nalgebra_glm::Vec3, view_dir: &nalgebra_glm::Vec3, roughness: f32) -> f32 {
+   if roughness <= 0.0 {
+      return 1.0;
+   }
+   let sigma2 = roughness * roughness;
+   let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+   let b = 0.45 * sigma2 / (sigma2 + 0.09);
+   
+   let cos_in = normal.dot(light_dir).clamp(0.0, 1.0);
+   let cos_out = normal.dot(view_dir).clamp(0.0, 1.0);
+   let theta_in = cos_in.acos();
+   let theta_out = cos_out.acos();
+   let alpha = theta_in.max(theta_out);
+   let beta = theta_in.min(theta_out);
+   
+   // Coseno del ángulo azimutal entre luz y vista, proyectadas sobre el plano tangente
+   let light_tangent = light_dir - normal * cos_in;
+   let view_tangent = view_dir - normal * cos_out;
+   let cos_azimuth = if light_tangent.norm_squared() > 1e-8 && view_tangent.norm_squared() > 1e-8 {
+      light_tangent.normalize().dot(&view_tangent.normalize()).max(0.0)
+   } else {
+      0.0
+   };
+   
+   a + b * cos_azimuth * alpha.sin() * beta.tan()
+}
+
+// Fresnel de Schlick simplificado: 0 mirando de frente, 1 en el limbo
+fn fresnel(normal: &nalgebra_glm::Vec3, view_dir: &nalgebra_glm::Vec3, power: f32) -> f32 {
+   (1.0 - normal.dot(view_dir).max(0.0)).powf(power)
+}
+
 // ============================================
 // SUN SHADER - Estrella con efecto de plasma
 // ============================================
-fn sun_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-   let position = fragment.vertex_position;
-   let time = uniforms.time;
+fn sun_shader(fragment: &Fragment, ctx: &FrameContext) -> Color {
+   sun_emission(fragment, ctx).tone_map()
+}
+
+fn sun_emission(fragment: &Fragment, ctx: &FrameContext) -> EmissiveAccumulator {
+   let position = ctx.sample_position(fragment);
+   let time = ctx.time;
+   
+   // Fotosfera más chica que la geometría: afuera del disco solo queda corona
+   if let Some(photosphere_radius) = ctx.uniforms.params.sun.photosphere_radius {
+      let facing = fragment.normal_or_sphere().dot(&ctx.view_direction).clamp(0.0, 1.0);
+      let apparent_radius = (1.0 - facing * facing).sqrt();
+      
+      if apparent_radius > photosphere_radius {
+         let corona_falloff = 1.0 - smoothstep(photosphere_radius, 1.0, apparent_radius);
+         let mut corona = EmissiveAccumulator::default();
+         corona.add(Color::from_hex(0xFF8C00), corona_falloff * corona_falloff * ctx.uniforms.emissive_scale);
+         return corona;
+      }
+   }
    
    // Capa 1: Base de colores cálidos con gradiente radial
    let distance_from_center = (position.x * position.x + 
@@ -154,66 +518,153 @@ fn sun_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    let edge_color = Color::from_hex(0xFF8C00);  // Naranja dorado
    
    let base_color = if distance_from_center < 0.5 {
-      let t = distance_from_center * 2.0;
+      let t = remap(distance_from_center, 0.0, 0.5, 0.0, 1.0);
       lerp_color(&core_color, &mid_color, t)
    } else {
-      let t = (distance_from_center - 0.5) * 2.0;
+      let t = remap(distance_from_center, 0.5, 1.0, 0.0, 1.0);
       lerp_color(&mid_color, &edge_color, t)
    };
    
    // Capa 2: Plasma animado usando noise
    let plasma_zoom = 8.0;
    let plasma_speed = 0.3;
-   let plasma_noise = uniforms.noise.get_noise_3d(
+   let plasma_noise = ctx.noise.get_noise_3d(
       position.x * plasma_zoom + time * plasma_speed,
       position.y * plasma_zoom,
       position.z * plasma_zoom + time * plasma_speed * 0.5,
    );
    
+   // Las capas emisivas se suman y se comprimen una sola vez al final
+   let mut emission = EmissiveAccumulator::from_color(base_color);
+   
    let plasma_intensity = (plasma_noise + 1.0) * 0.5;
    let plasma_color = Color::from_hex(0xFFAA00);
-   let with_plasma = blend_colors(&base_color, &plasma_color, plasma_intensity * 0.3);
+   emission.add(plasma_color, plasma_intensity * ctx.uniforms.params.sun.plasma_blend);
+   
+   // Capa 2b: Granulación, celdas de convección que titilan cada una a su ritmo
+   let params = &ctx.uniforms.params.sun;
+   if detail_layer_enabled(ctx, 1) {
+      let granulation_zoom = 12.0;
+      let cell_value = ctx.cell_noise.get_noise_3d(
+         position.x * granulation_zoom,
+         position.y * granulation_zoom,
+         position.z * granulation_zoom,
+      );
+      
+      // Fase y velocidad derivadas del valor de la celda: el promedio global se mantiene
+      let cell_phase = cell_value * 40.0;
+      let cell_rate = params.granulation_flicker_speed * (0.75 + 0.5 * cell_value.abs());
+      let cell_brightness = (time * cell_rate * 2.0 * std::f32::consts::PI + cell_phase).sin();
+      let granulation_amount = cell_brightness.abs() * params.granulation_strength;
+      if cell_brightness > 0.0 {
+         emission.add(Color::from_hex(0xFFF3B0), granulation_amount); // Celda caliente
+      } else {
+         emission.mix(Color::from_hex(0xE07000), granulation_amount); // Celda más fría
+      }
+   }
    
    // Capa 3: Manchas solares (áreas más oscuras)
-   let spot_zoom = 3.0;
-   let spot_noise = uniforms.noise.get_noise_3d(
-      position.x * spot_zoom,
-      position.y * spot_zoom + time * 0.1,
-      position.z * spot_zoom,
-   );
+   let spot_noise = sunspot_noise(ctx, position);
    
    if spot_noise > 0.5 {
-      let spot_factor = (spot_noise - 0.5) * 2.0;
-      let dark_spot = Color::from_hex(0x994400);
-      let with_spots = blend_colors(&with_plasma, &dark_spot, spot_factor * 0.4);
+      // Penumbra en todo el borde de la mancha, umbra oscura solo hacia el centro
+      let spot_factor = smoothstep(0.5, 1.0, spot_noise);
+      let umbra_factor = smoothstep(params.umbra_threshold, 1.0, spot_factor);
+      emission.mix(params.spot_color, spot_factor * params.spot_darkness);
+      emission.mix(params.umbra_color, umbra_factor * params.spot_darkness);
       
       // Capa 4: Brillo en los bordes (efecto corona)
-      let edge_glow = (1.0 - distance_from_center).powf(3.0);
+      let edge_glow = saturate(1.0 - distance_from_center).powf(3.0);
       let glow_color = Color::from_hex(0xFFFFAA);
-      blend_colors(&with_spots, &glow_color, edge_glow * 0.3)
-   } else {
-      with_plasma
+      emission.add(glow_color, edge_glow * params.edge_glow_blend);
    }
+   
+   // Capa 5: Espículas, filamentos finos que titilan a lo largo del limbo
+   if params.spicule_intensity > 0.0 {
+      let normal = fragment.normal_or_sphere();
+      let limb = fresnel(&normal, &ctx.view_direction, 4.0);
+      
+      if limb > 0.01 {
+         // Ángulo alrededor del centro del disco: los filamentos cambian a lo largo
+         // del borde y se mantienen en la dirección radial
+         let view = ctx.view_direction;
+         let up = if view.y.abs() < 0.99 {
+            nalgebra_glm::Vec3::new(0.0, 1.0, 0.0)
+         } else {
+            nalgebra_glm::Vec3::new(1.0, 0.0, 0.0)
+         };
+         let side = up.cross(&view).normalize();
+         let disk_up = view.cross(&side);
+         let angle = normal.dot(&disk_up).atan2(normal.dot(&side));
+         
+         // Círculo en el espacio del noise con unos `spicule_frequency` filamentos por vuelta
+         // (con la frecuencia base del noise, 0.01, sale ~1 filamento cada 25 de radio)
+         let spicule_radius = params.spicule_frequency * 25.0;
+         let spicule_noise = ctx.noise.get_noise_3d(
+            angle.cos() * spicule_radius,
+            angle.sin() * spicule_radius,
+            time * 40.0,
+         );
+         let filament = saturate(spicule_noise * 0.5 + 0.5).powi(3);
+         emission.add(Color::from_hex(0xFFE0A0), limb * filament * params.spicule_intensity);
+      }
+   }
+   
+   emission.scale(ctx.uniforms.emissive_scale);
+   emission
+}
+
+// Desplazamiento del muestreo de las manchas a este tiempo (en coordenadas del noise)
+fn sunspot_drift(ctx: &FrameContext) -> nalgebra_glm::Vec3 {
+   ctx.uniforms.params.sun.spot_drift_velocity * ctx.time
+}
+
+// Con la frecuencia base del ruido hace falta un zoom alto para que haya manchas
+// sueltas en el disco en vez de un valor casi igual en toda la estrella
+const SUNSPOT_ZOOM: f32 = 20.0;
+
+// Noise de las manchas solares: pasa de 0.5 dentro de una mancha y llega a 1 en su centro
+fn sunspot_noise(ctx: &FrameContext, position: nalgebra_glm::Vec3) -> f32 {
+   let spot_drift = sunspot_drift(ctx);
+   ctx.noise.get_noise_3d(
+      position.x * SUNSPOT_ZOOM + spot_drift.x,
+      position.y * SUNSPOT_ZOOM + spot_drift.y,
+      position.z * SUNSPOT_ZOOM + spot_drift.z,
+   )
 }
 
 // ============================================
 // ROCKY PLANET SHADER - Planeta tipo Marte
 // ============================================
-fn rocky_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-   let position = fragment.vertex_position;
-   let time = uniforms.time;
+fn rocky_planet_shader(fragment: &Fragment, ctx: &FrameContext, light: &LightingResult) -> Color {
+   let position = ctx.sample_position(fragment);
    
    // Capa 1: Terreno marciano base
+   let params = &ctx.uniforms.params.rocky;
+   let external = ctx.uniforms.terrain_provider.as_ref().map(|provider| provider(position));
    let terrain_zoom = 4.0;
-   let terrain_noise = uniforms.noise.get_noise_3d(
-      position.x * terrain_zoom,
-      position.y * terrain_zoom,
-      position.z * terrain_zoom,
-   );
+   let mut terrain_noise = match external {
+      Some((height, _)) => height,
+      None => ctx.noise.get_noise_3d(
+         position.x * terrain_zoom,
+         position.y * terrain_zoom,
+         position.z * terrain_zoom,
+      ),
+   };
+   
+   // Noise "ridged" secundario para costas y mesetas más interesantes
+   if let (Some(mode), None) = (params.terrain_blend, external) {
+      let ridge_zoom = 6.0;
+      let ridge_noise = 1.0 - 2.0 * ctx.noise.get_noise_3d(
+         position.x * ridge_zoom + 300.0,
+         position.y * ridge_zoom,
+         position.z * ridge_zoom,
+      ).abs();
+      terrain_noise = blend_noise(terrain_noise, ridge_noise, mode);
+   }
    
-   // Capa base con variación de rugosidad
-   let base_noise = terrain_noise.abs();
-   let terrain_roughness = base_noise * 0.7 + 0.3;
+   // Capa base con variación de rugosidad según la edad de la superficie
+   let terrain_roughness = terrain_roughness(params.surface_age, terrain_noise);
    
    // Colores base de Marte (más uniforme en tonos rojizos)
    let rust_red = Color::from_hex(0xB22222);    // Rojo ladrillo
@@ -221,54 +672,274 @@ fn rocky_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    let iron_oxide = Color::from_hex(0x8B4513);  // Marrón silla
    
    let mut base_color = if terrain_roughness > 0.6 {
-      blend_colors(&rust_red, &iron_oxide, (terrain_roughness - 0.6) / 0.4)
+      blend_colors(&rust_red, &iron_oxide, remap(terrain_roughness, 0.6, 1.0, 0.0, 1.0))
    } else {
-      blend_colors(&mars_dust, &rust_red, terrain_roughness / 0.6)
-   };   // Capa 2: Detalles de superficie marciana (dunas, cráteres)
-   let detail_zoom = 10.0;
-   let detail_noise = uniforms.noise.get_noise_3d(
-      position.x * detail_zoom + 100.0,
-      position.y * detail_zoom,
-      position.z * detail_zoom,
-   );
+      blend_colors(&mars_dust, &rust_red, remap(terrain_roughness, 0.0, 0.6, 0.0, 1.0))
+   };
    
-   let detail_color = if detail_noise > 0.3 {
-      Color::from_hex(0xF4A460) // Arena/dunas
-   } else {
-      Color::from_hex(0xA0522D) // Roca marciana
+   // Con un mapa externo, el bioma elige la paleta; la roca conserva la marciana
+   // y el océano se resuelve en la capa de agua
+   if let Some((_, biome)) = external {
+      let shade = remap(terrain_roughness, 0.3, 1.0, 0.0, 1.0);
+      match biome {
+         Biome::Desert => base_color = blend_colors(&mars_dust, &Color::from_hex(0xF4A460), shade),
+         Biome::Forest => base_color = blend_colors(&Color::from_hex(0x2E4A1E), &Color::from_hex(0x4F6B2A), shade),
+         Biome::Ice => base_color = params.polar_color,
+         Biome::Rock | Biome::Ocean => {}
+      }
+   }   // Capa 2: Detalles de superficie marciana (dunas, cráteres)
+   if detail_layer_enabled(ctx, 1) {
+      let detail_zoom = 10.0;
+      let detail_noise = ctx.noise.get_noise_3d(
+         position.x * detail_zoom + 100.0,
+         position.y * detail_zoom,
+         position.z * detail_zoom,
+      );
+      
+      let detail_color = if detail_noise > 0.3 {
+         Color::from_hex(0xF4A460) // Arena/dunas
+      } else {
+         Color::from_hex(0xA0522D) // Roca marciana
+      };
+      base_color = blend_colors(&base_color, &detail_color, detail_noise.abs() * params.detail_blend);
+   }
+   
+   // Capa 2c: Edad de la superficie. Las viejas acumulan cráteres, las jóvenes
+   // muestran vetas de lava; con edad 0.5 no aparece ninguno de los dos
+   let (crater_depth, lava_factor) = surface_age_features(ctx, position);
+   if crater_depth > 0.0 {
+      let crater_color = Color::from_hex(0x5a2a1a);
+      base_color = blend_colors(&base_color, &crater_color, crater_depth * 0.6);
+   }
+   if lava_factor > 0.0 {
+      base_color = blend_colors(&base_color, &Color::from_hex(0x2a1a16), lava_factor); // Basalto
+   }
+   
+   // Capa 2b: Océanos opcionales, de agua somera en la costa a profunda mar adentro
+   let water_factor = match (params.sea_level, external) {
+      // El mapa externo decide qué es océano; la altura solo da la profundidad
+      (sea_level, Some((height, biome))) => {
+         if biome == Biome::Ocean {
+            let depth = sea_level.unwrap_or(0.0) - height;
+            base_color = lerp_color(&params.shallow_water_color, &params.deep_water_color, smoothstep(0.0, 0.3, depth));
+            1.0
+         } else {
+            0.0
+         }
+      }
+      (Some(sea_level), None) => {
+         let half_coast = params.coastline_width * 0.5;
+         let depth = sea_level - terrain_noise;
+         let water_color = lerp_color(&params.shallow_water_color, &params.deep_water_color, smoothstep(0.0, 0.3, depth));
+         let water_factor = smoothstep(-half_coast, half_coast, depth);
+         base_color = blend_colors(&base_color, &water_color, water_factor);
+         water_factor
+      }
+      (None, None) => 0.0,
    };
-   base_color = blend_colors(&base_color, &detail_color, detail_noise.abs() * 0.4);
-   
-   // Capa 3: Tormentas de polvo marcianas
-   let dust_zoom = 8.0;
-   let dust_speed = 0.1;
-   let dust_noise = uniforms.noise.get_noise_3d(
-      position.x * dust_zoom + time * dust_speed,
-      position.y * dust_zoom,
-      position.z * dust_zoom + time * dust_speed * 0.3,
-   );
    
-   if dust_noise > 0.6 {
-      let dust_factor = (dust_noise - 0.6) / 0.4;
-      let dust_color = Color::from_hex(0xD2691E); // Color polvo rojizo
-      base_color = blend_colors(&base_color, &dust_color, dust_factor * 0.3);
+   // Capa 3: Tormentas de polvo marcianas (las "nubes" de este planeta), una o más capas
+   for (layer_index, layer) in params.storm_layers.iter().enumerate() {
+      // Sombra de la tormenta: la que está en altura hacia la luz tapa este punto
+      if layer.altitude > 0.0 {
+         let shadow_factor = dust_storm_factor(ctx, layer, layer_index, dust_shadow_position(ctx, layer, position));
+         if shadow_factor > 0.0 {
+            base_color = base_color * (1.0 - shadow_factor * params.cloud_shadow_blend);
+         }
+      }
+      
+      let dust_factor = dust_storm_factor(ctx, layer, layer_index, position);
+      if dust_factor > 0.0 {
+         let dust_color = Color::from_hex(0xD2691E); // Color polvo rojizo
+         base_color = blend_colors(&base_color, &dust_color, dust_factor * params.cloud_blend);
+      }
    }
    
+   // Capa 4: Casquetes polares de hielo con borde irregular. Con un mapa externo el
+   // hielo es el que marca el bioma, no la latitud
+   let latitude = position.normalize().y.abs();
+   let polar_edge = 0.85 + terrain_noise * 0.05;
+   let polar_factor = if let Some((_, biome)) = external {
+      if biome == Biome::Ice { 1.0 } else { 0.0 }
+   } else if params.soft_polar_edge {
+      smoothstep(polar_edge - 0.04, polar_edge + 0.04, latitude)
+   } else if latitude > polar_edge {
+      1.0
+   } else {
+      0.0
+   };
+   base_color = blend_colors(&base_color, &params.polar_color, polar_factor);
+   
+   base_color = surface_albedo(base_color, fragment, ctx);
+   
    // Aplicar iluminación suave para ver todo el planeta
-   let light_intensity = fragment.intensity * 0.7 + 0.3; // Mínimo 30% de luz ambiente
-   base_color * light_intensity
+   let (diffuse, ambient) = (light.diffuse * 0.7, 0.3 * light.ambient); // Mínimo 30% de luz ambiente
+   let mut lit_color = apply_light(base_color, diffuse, ambient, params.albedo_floor, ctx);
+   
+   // Capa 5: Atmósfera de polvo, más densa hacia el limbo
+   if params.dust_density > 0.0 {
+      let limb = fresnel(&fragment.normal_or_sphere(), &ctx.view_direction, 3.0);
+      let dust_amount = params.dust_density * (0.25 + 0.75 * limb);
+      let lit_dust = apply_light(params.dust_color, diffuse, ambient, params.albedo_floor, ctx);
+      lit_color = blend_colors(&lit_color, &lit_dust, dust_amount);
+   }
+   
+   // Capa 5b: Reflejo del entorno en hielo y océanos, más fuerte en ángulos rasantes
+   if let Some(environment) = &ctx.uniforms.environment {
+      let reflectivity = water_factor.max(polar_factor);
+      
+      if reflectivity > 0.0 {
+         let normal = fragment.normal_or_sphere();
+         let view = ctx.view_direction;
+         let reflected = normal * (2.0 * normal.dot(&view)) - view;
+         let env_weight = reflectivity * (0.04 + 0.96 * fresnel(&normal, &view, 5.0));
+         lit_color = blend_colors(&lit_color, &environment(reflected), env_weight);
+      }
+   }
+   
+   // Brillo propio de la lava, visible también en el lado nocturno
+   if lava_factor > 0.0 {
+      let lava_color = Color::from_hex(0xff5a1a);
+      lit_color = lit_color + lava_color * (lava_factor * (1.0 - water_factor) * ctx.uniforms.emissive_scale);
+   }
+   
+   // Capa 6: Luces de ciudades en el lado nocturno, con trazado de calles en zonas densas
+   if params.city_light_brightness > 0.0 {
+      let night_factor = smoothstep(0.2, 0.0, light.diffuse);
+      let city_density = city_density(ctx, position) * (1.0 - water_factor) * (1.0 - polar_factor);
+      
+      if night_factor > 0.0 && city_density > 0.0 {
+         let scale = params.city_grid_scale;
+         let grid = (position.x * scale).sin() * (position.z * scale).sin();
+         let street = smoothstep(0.15, 0.0, grid.abs());
+         let glow = street * city_density * night_factor * params.city_light_brightness * ctx.uniforms.emissive_scale;
+         lit_color = lit_color + params.city_light_color * glow;
+      }
+   }
+   
+   // Brillo especular del hielo cuando mira hacia el sol; refleja el color de la estrella
+   if polar_factor > 0.0 {
+      let ice_shininess = 48.0;
+      let glint = specular(&fragment.normal_or_sphere(), &ctx.uniforms.light_direction, &ctx.view_direction, ice_shininess);
+      lit_color = lit_color + ctx.uniforms.light_color * (glint * light.specular * polar_factor * 0.8);
+   }
+   
+   // Reflejo del sol sobre el océano, más concentrado que el del hielo
+   if water_factor > 0.0 {
+      let water_shininess = 96.0;
+      let glint = specular(&fragment.normal_or_sphere(), &ctx.uniforms.light_direction, &ctx.view_direction, water_shininess);
+      let glint_color = ctx.uniforms.light_color.modulate(&params.glint_color);
+      lit_color = lit_color + glint_color * (glint * light.specular * water_factor);
+   }
+   
+   lit_color
 }
 
 // ============================================
 // GAS GIANT SHADER - Planeta tipo Júpiter/Saturno
 // ============================================
-fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-   let position = fragment.vertex_position;
-   let time = uniforms.time;
+// Las capas finas solo muestrean ruido con calidad suficiente; en los tests se cuenta
+// cuántas veces se entra en una, para comprobar que calidad 0 ahorra esos muestreos
+fn detail_layer_enabled(ctx: &FrameContext, min_level: u8) -> bool {
+   let enabled = ctx.uniforms.detail_level >= min_level;
+   #[cfg(test)]
+   if enabled {
+      tests::DETAIL_LAYER_SAMPLES.with(|samples| samples.set(samples.get() + 1));
+   }
+   enabled
+}
+
+// Densidad de ciudades (0..1): manchas de unas pocas por hemisferio, vacías en el resto
+fn city_density(ctx: &FrameContext, position: nalgebra_glm::Vec3) -> f32 {
+   // Con la frecuencia base del ruido hace falta un zoom alto para que la densidad
+   // cambie de una región a otra en vez de ser casi la misma en todo el planeta
+   let city_zoom = 20.0;
+   let city_noise = ctx.noise.get_noise_3d(
+      position.x * city_zoom + 900.0,
+      position.y * city_zoom,
+      position.z * city_zoom,
+   );
+   smoothstep(0.1, 0.4, city_noise)
+}
+
+// Rugosidad del terreno: las superficies jóvenes son lisas y volcánicas y las viejas
+// se vuelven más accidentadas a la par que los cráteres (edad 0.5 = rugosidad original)
+fn terrain_roughness(surface_age: f32, terrain_noise: f32) -> f32 {
+   let ruggedness = 1.0 + (surface_age - 0.5) * 0.8;
+   terrain_noise.abs() * 0.7 * ruggedness + 0.3
+}
+
+// Rasgos que dependen de la edad de la superficie, en 0..1: profundidad del cráter
+// (superficies viejas) y presencia de vetas de lava (superficies jóvenes)
+fn surface_age_features(ctx: &FrameContext, position: nalgebra_glm::Vec3) -> (f32, f32) {
+   let surface_age = ctx.uniforms.params.rocky.surface_age;
+   
+   let crater_density = saturate((surface_age - 0.5) * 2.0);
+   let crater_depth = if crater_density > 0.0 {
+      // Más bajo que en la luna: con esta escala el noise rara vez pasa de 0.6
+      let crater_threshold = 0.6 - 0.4 * crater_density;
+      let crater_noise = crater_noise_at(ctx, position, 60.0, 1200.0);
+      if crater_noise > crater_threshold {
+         smoothstep(crater_threshold, 1.0, crater_noise)
+      } else {
+         0.0
+      }
+   } else {
+      0.0
+   };
+   
+   let lava_amount = saturate((0.5 - surface_age) * 2.0);
+   let lava_factor = if lava_amount > 0.0 {
+      let vein_zoom = 80.0;
+      let vein_noise = ctx.noise.get_noise_3d(
+         position.x * vein_zoom + 1500.0,
+         position.y * vein_zoom,
+         position.z * vein_zoom,
+      );
+      // Noise "ridged": las vetas siguen las líneas donde el noise cruza por cero
+      let vein_width = 0.08 * lava_amount;
+      smoothstep(vein_width, 0.0, vein_noise.abs())
+   } else {
+      0.0
+   };
+   
+   (crater_depth, lava_factor)
+}
+
+// Cuánto cubre la capa de tormentas el punto: 0 fuera de la tormenta, 1 en su centro
+fn dust_storm_factor(ctx: &FrameContext, layer: &DustStormLayer, layer_index: usize, position: nalgebra_glm::Vec3) -> f32 {
+   let time = ctx.surface_time;
+   // Cada capa lee otra zona del noise para que no coincidan entre sí
+   let layer_offset = layer_index as f32 * 250.0;
+   let dust_noise = ctx.noise.get_noise_3d(
+      position.x * layer.zoom + time * layer.speed + layer_offset,
+      position.y * layer.zoom,
+      position.z * layer.zoom + time * layer.speed * 0.3,
+   );
+   let storm_threshold = 1.0 - layer.coverage;
+   if dust_noise > storm_threshold {
+      smoothstep(storm_threshold, 1.0, dust_noise)
+   } else {
+      0.0
+   }
+}
+
+// Punto de la capa que le hace sombra a `position`: `altitude` más arriba, hacia la luz
+fn dust_shadow_position(ctx: &FrameContext, layer: &DustStormLayer, position: nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+   position + ctx.object_light_direction * layer.altitude
+}
+
+fn gas_giant_shader(fragment: &Fragment, ctx: &FrameContext, light: &LightingResult) -> Color {
+   let position = ctx.sample_position(fragment);
+   let params = &ctx.uniforms.params.gas_giant;
+   
+   // Capa 0: Vórtice polar, con las bandas onduladas en seis lóbulos
+   let (swirled, vortex_factor) = polar_vortex_swirl(ctx, position);
+   let vortex_lobes = (swirled.z.atan2(swirled.x) * 6.0).sin();
    
    // Capa 1: Bandas horizontales base
    let band_frequency = 15.0;
-   let band_position = position.y * band_frequency;
+   let band_position = position.y * band_frequency + vortex_factor * vortex_lobes * 2.0;
    
    // Colores de las bandas
    let color1 = Color::from_hex(0xd4a574); // Beige claro
@@ -276,7 +947,7 @@ fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    let color3 = Color::from_hex(0x8b6239); // Marrón oscuro
    let color4 = Color::from_hex(0xe6c9a8); // Crema
    
-   let band_value = (band_position.sin() + 1.0) * 0.5;
+   let band_value = sharpen_band((band_position.sin() + 1.0) * 0.5, params.band_sharpness);
    let base_color = if band_value < 0.25 {
       let t = band_value * 4.0;
       lerp_color(&color1, &color2, t)
@@ -291,12 +962,14 @@ fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
       lerp_color(&color4, &color1, t)
    };
    
+   let flow_time = gas_giant_flow_time(ctx);
+   
    // Capa 2: Turbulencias en las bandas
    let turbulence_zoom = 8.0;
-   let turbulence_noise = uniforms.noise.get_noise_3d(
-      position.x * turbulence_zoom + time * 0.3,
-      position.y * turbulence_zoom * 0.5,
-      position.z * turbulence_zoom,
+   let turbulence_noise = ctx.noise.get_noise_3d(
+      swirled.x * turbulence_zoom + flow_time * 0.3,
+      swirled.y * turbulence_zoom * 0.5,
+      swirled.z * turbulence_zoom,
    );
    
    let turbulent_offset = turbulence_noise * 0.3;
@@ -308,47 +981,110 @@ fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
       Color::from_hex(0xe8d4b8)
    };
    
-   let with_turbulence = blend_colors(&base_color, &turbulence_color, turbulence_noise.abs() * 0.4);
+   let with_turbulence = blend_colors(&base_color, &turbulence_color, turbulence_noise.abs() * params.turbulence_blend);
+   
+   let (diffuse, ambient) = (light.diffuse * 0.7, 0.3 * light.ambient);
+   
+   // Capa 2b: Tormentas adicionales repartidas por la esfera
+   let mut with_storms = with_turbulence;
+   let direction = position.normalize();
+   for (storm_index, (storm, center)) in params.storms.iter().zip(&ctx.storm_centers).enumerate() {
+      let distance_to_storm = (direction - center).norm() / storm.radius;
+      if distance_to_storm < 1.0 {
+         let storm_noise = ctx.noise.get_noise_3d(
+            position.x * 5.0 + flow_time * 0.05 + storm_index as f32 * 50.0,
+            position.y * 5.0,
+            position.z * 5.0,
+         );
+         let storm_factor = saturate(1.0 - distance_to_storm) * ((storm_noise + 1.0) * 0.5);
+         with_storms = blend_colors(&with_storms, &storm.tinted_color(), storm_factor * params.storm_blend);
+      }
+   }
    
    // Capa 3: Gran Mancha Roja (o equivalente)
+   // Distancia elíptica normalizada: 1.0 en el borde de la tormenta
    let spot_center_x = 0.3;
    let spot_center_y = 0.2;
-   let distance_to_spot = ((position.x - spot_center_x).powi(2) + 
-                           (position.y - spot_center_y).powi(2)).sqrt();
+   let distance_to_spot = (((position.x - spot_center_x) / params.storm_radius_x).powi(2) + 
+                           ((position.y - spot_center_y) / params.storm_radius_y).powi(2)).sqrt();
    
-   if distance_to_spot < 0.3 {
-      let spot_noise = uniforms.noise.get_noise_3d(
-         position.x * 5.0 + time * 0.05,
+   if distance_to_spot < 1.0 {
+      let spot_noise = ctx.noise.get_noise_3d(
+         position.x * 5.0 + flow_time * 0.05,
          position.y * 5.0,
          position.z * 5.0,
       );
       
-      let spot_factor = (1.0 - distance_to_spot / 0.3) * ((spot_noise + 1.0) * 0.5);
-      let spot_color = Color::from_hex(0xc74440); // Rojo
-      let with_spot = blend_colors(&with_turbulence, &spot_color, spot_factor * 0.7);
+      let spot_factor = saturate(1.0 - distance_to_spot) * ((spot_noise + 1.0) * 0.5);
+      let with_spot = blend_colors(&with_storms, &ctx.great_spot_color, spot_factor * params.great_spot_blend);
       
       // Capa 4: Detalles finos y remolinos
-      let detail_zoom = 20.0;
-      let detail_noise = uniforms.noise.get_noise_3d(
-         position.x * detail_zoom - time * 0.2,
-         position.y * detail_zoom,
-         position.z * detail_zoom,
-      );
-      
-      let detail_color = Color::from_hex(0xf5e6d3);
-      let final_color = blend_colors(&with_spot, &detail_color, detail_noise.abs() * 0.2);
+      let final_color = if detail_layer_enabled(ctx, 2) {
+         let detail_zoom = 20.0;
+         let detail_flow = spot_detail_offset(ctx);
+         let detail_noise = ctx.noise.get_noise_3d(
+            position.x * detail_zoom + detail_flow.x,
+            position.y * detail_zoom + detail_flow.y,
+            position.z * detail_zoom + detail_flow.z,
+         );
+         
+         let detail_color = Color::from_hex(0xf5e6d3);
+         blend_colors(&with_spot, &detail_color, detail_noise.abs() * params.detail_blend)
+      } else {
+         with_spot
+      };
       
-      final_color * (fragment.intensity * 0.7 + 0.3)
+      apply_light(surface_albedo(final_color, fragment, ctx), diffuse, ambient, params.albedo_floor, ctx)
    } else {
-      with_turbulence * (fragment.intensity * 0.7 + 0.3)
+      apply_light(surface_albedo(with_storms, fragment, ctx), diffuse, ambient, params.albedo_floor, ctx)
    }
 }
 
+// Por encima de vortex_latitude el muestreo gira alrededor del polo, más cuanto más
+// cerca. Devuelve la posición girada y cuánto pesa el vórtice (0 = fuera de él)
+fn polar_vortex_swirl(ctx: &FrameContext, position: nalgebra_glm::Vec3) -> (nalgebra_glm::Vec3, f32) {
+   let params = &ctx.uniforms.params.gas_giant;
+   let latitude = position.normalize().y;
+   let vortex_factor = if params.vortex_strength > 0.0 {
+      smoothstep(params.vortex_latitude, 1.0, latitude.abs())
+   } else {
+      0.0
+   };
+   if vortex_factor <= 0.0 {
+      return (position, 0.0);
+   }
+   let vortex_rotation = if latitude >= 0.0 {
+      ctx.rotations.polar_vortex
+   } else {
+      ctx.rotations.polar_vortex.transpose()
+   };
+   // Mezclar con la posición original retuerce el patrón: el centro gira entero, el borde apenas
+   (position + (vortex_rotation * position - position) * vortex_factor, vortex_factor)
+}
+
+// Tiempo con signo: invertir flow_direction invierte toda la deriva de la atmósfera
+fn gas_giant_flow_time(ctx: &FrameContext) -> f32 {
+   ctx.surface_time * ctx.uniforms.params.gas_giant.flow_direction
+}
+
+// Desplazamiento del muestreo de los remolinos de la gran mancha: sigue su propia
+// velocidad, con el mismo sentido de flujo que el resto de la atmósfera
+fn spot_detail_offset(ctx: &FrameContext) -> nalgebra_glm::Vec3 {
+   ctx.uniforms.params.gas_giant.spot_detail_velocity * gas_giant_flow_time(ctx)
+}
+
+// Curva de contraste alrededor de 0.5: empuja el valor de la banda hacia 0 o 1
+fn sharpen_band(value: f32, sharpness: f32) -> f32 {
+   let centered = value * 2.0 - 1.0;
+   let sharpened = centered.signum() * centered.abs().powf(1.0 / (1.0 + sharpness.max(0.0)));
+   sharpened * 0.5 + 0.5
+}
+
 // ============================================
 // MOON SHADER - Luna con cráteres
 // ============================================
-fn moon_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-   let position = fragment.vertex_position;
+fn moon_shader(fragment: &Fragment, ctx: &FrameContext, light: &LightingResult) -> Color {
+   let position = ctx.sample_position(fragment);
    
    // Capa 1: Color base grisáceo
    let base_color = Color::from_hex(0x9b9b9b); // Gris medio
@@ -357,7 +1093,7 @@ fn moon_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    
    // Capa 2: Variaciones de terreno
    let terrain_zoom = 8.0;
-   let terrain_noise = uniforms.noise.get_noise_3d(
+   let terrain_noise = ctx.noise.get_noise_3d(
       position.x * terrain_zoom,
       position.y * terrain_zoom,
       position.z * terrain_zoom,
@@ -369,51 +1105,216 @@ fn moon_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
       lerp_color(&base_color, &dark_color, -terrain_noise)
    };
    
-   // Capa 3: Cráteres
-   let crater_zoom = 10.0;
-   let crater_noise = uniforms.noise.get_noise_3d(
-      position.x * crater_zoom + 500.0,
-      position.y * crater_zoom,
-      position.z * crater_zoom,
-   );
-   
+   // Capa 3: Cráteres en tres escalas (pocos grandes, muchos pequeños)
+   let params = &ctx.uniforms.params.moon;
    let mut final_color = terrain_color;
    
-   if crater_noise > 0.7 {
-      let crater_depth = (crater_noise - 0.7) / 0.3;
-      let crater_color = Color::from_hex(0x4a4a4a); // Muy oscuro
-      final_color = blend_colors(&final_color, &crater_color, crater_depth * 0.8);
+   // Con calidad 0 solo quedan los cráteres grandes y medianos
+   let crater_layer_count = if detail_layer_enabled(ctx, 1) { 3 } else { 2 };
+   
+   for (layer_index, (crater_zoom, crater_offset, density)) in crater_layers(params).into_iter().take(crater_layer_count).enumerate() {
+      if density <= 0.0 {
+         continue;
+      }
+      
+      let crater_threshold = crater_threshold(density);
+      let crater_noise = crater_noise_at(ctx, position, crater_zoom, crater_offset);
+      
+      if crater_noise > crater_threshold {
+         let crater_depth = smoothstep(crater_threshold, 1.0, crater_noise);
+         // Fondo plano: la pared cae rápido y el resto del cráter queda igual de oscuro
+         let floor_depth = smoothstep(0.0, 0.25, crater_depth);
+         let darkening = crater_depth + (floor_depth - crater_depth) * params.crater_floor_flatness;
+         let crater_color = Color::from_hex(0x4a4a4a); // Muy oscuro
+         final_color = blend_colors(&final_color, &crater_color, darkening * params.crater_blend);
+         
+         // Pico central: solo en los cráteres grandes, y solo en algunos. El id sale de la
+         // celda donde cae el centro estimado, así todo el cráter toma la misma decisión
+         if layer_index == 0 && params.central_peak_probability > 0.0 {
+            if let Some(center) = crater_center(ctx, position, crater_zoom, crater_offset) {
+               let id_position = center * crater_zoom * CRATER_ID_FREQUENCY;
+               let crater_id = ctx.cell_noise.get_noise_3d(id_position.x, id_position.y, id_position.z);
+               if (crater_id + 1.0) * 0.5 < params.central_peak_probability {
+                  let peak_radius = 2.4 / crater_zoom;
+                  let peak_factor = smoothstep(peak_radius, 0.0, (center - position).norm());
+                  let peak_color = Color::from_hex(0xd0d0d0);
+                  final_color = blend_colors(&final_color, &peak_color, peak_factor * params.central_peak_brightness);
+               }
+            }
+         }
+      } else {
+         // Capa 3b: Borde brillante y rayos de eyección alrededor del cráter
+         let ejecta_color = Color::from_hex(0xe8e8e8);
+         let rim_width = 0.05;
+         let distance_to_rim = crater_threshold - crater_noise;
+         
+         if distance_to_rim < rim_width {
+            let rim_factor = smoothstep(rim_width, 0.0, distance_to_rim);
+            final_color = blend_colors(&final_color, &ejecta_color, rim_factor * params.crater_rim_brightness);
+         }
+         
+         if params.ejecta_rays && distance_to_rim < params.ray_length {
+            let angle = crater_ray_angle(ctx, position, crater_zoom, crater_offset);
+            // El círculo de muestreo necesita un radio grande para que, con la frecuencia
+            // base del ruido, el ángulo recorra varias celdas: unos 12 rayos por cráter
+            let ray_radius = 300.0;
+            let ray_noise = ctx.noise.get_noise_2d(angle.cos() * ray_radius + crater_offset, angle.sin() * ray_radius);
+            
+            if ray_noise > 0.2 {
+               let ray_factor = remap(ray_noise, 0.2, 1.0, 0.0, 1.0);
+               let fade = smoothstep(params.ray_length, 0.0, distance_to_rim);
+               final_color = blend_colors(&final_color, &ejecta_color, ray_factor * fade * params.ray_brightness);
+            }
+         }
+      }
    }
    
    // Capa 4: Detalles de superficie
-   let detail_zoom = 25.0;
-   let detail_noise = uniforms.noise.get_noise_3d(
-      position.x * detail_zoom,
-      position.y * detail_zoom,
-      position.z * detail_zoom,
-   );
-   
-   let detail_color = Color::from_hex(0xb0b0b0);
-   final_color = blend_colors(&final_color, &detail_color, detail_noise.abs() * 0.15);
+   if detail_layer_enabled(ctx, 2) {
+      let detail_zoom = 25.0;
+      let detail_noise = ctx.noise.get_noise_3d(
+         position.x * detail_zoom,
+         position.y * detail_zoom,
+         position.z * detail_zoom,
+      );
+      
+      let detail_color = Color::from_hex(0xb0b0b0);
+      final_color = blend_colors(&final_color, &detail_color, detail_noise.abs() * params.detail_blend);
+   }
    
    // Aplicar iluminación suave para la luna
-   let light_intensity = fragment.intensity * 0.6 + 0.4; // Luz ambiente alta para la luna
-   final_color * light_intensity
+   let normal = fragment.normal_or_sphere();
+   let (lambert, light_dir) = match params.phase {
+      Some(phase) => {
+         let phase_light = phase_light_direction(ctx, phase);
+         (normal.dot(&phase_light).max(0.0), phase_light)
+      }
+      None => (light.diffuse, ctx.uniforms.light_direction),
+   };
+   let diffuse = lambert * oren_nayar_factor(&normal, &light_dir, &ctx.view_direction, params.oren_nayar_roughness);
+   // Luz ambiente alta para la luna; con una fase fija apenas queda la luz cenicienta,
+   // así la luna nueva se ve oscura y el terminador queda limpio
+   let ambient = if params.phase.is_some() { 0.03 * light.ambient } else { 0.4 * light.ambient };
+   let lit_color = apply_light(surface_albedo(final_color, fragment, ctx), diffuse * 0.6, ambient, params.albedo_floor, ctx);
+   
+   // Capa 5: Luz de contorno en el limbo
+   if params.rim_strength > 0.0 {
+      let rim = fresnel(&fragment.normal_or_sphere(), &ctx.view_direction, 3.0);
+      blend_colors(&lit_color, &params.rim_color, rim * params.rim_strength)
+   } else {
+      lit_color
+   }
 }
 
-// ============================================
-// RINGS SHADER - Anillos con partículas de hielo y rocas
-// ============================================
-fn rings_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-   let position = fragment.vertex_position;
-   let time = uniforms.time;
-   
-   // Calcular distancia desde el centro para crear anillos concéntricos
-   let distance_from_center = (position.x * position.x + position.z * position.z).sqrt();
+// Luz sintética de una fase lunar: gira alrededor del cuerpo en el plano
+// horizontal de la cámara, desde atrás (luna nueva) hasta detrás de la cámara (llena)
+fn phase_light_direction(ctx: &FrameContext, phase: f32) -> nalgebra_glm::Vec3 {
+   let angle = phase * 2.0 * std::f32::consts::PI;
+   let view = ctx.view_direction;
+   let up = if view.y.abs() < 0.99 {
+      nalgebra_glm::Vec3::new(0.0, 1.0, 0.0)
+   } else {
+      nalgebra_glm::Vec3::new(1.0, 0.0, 0.0)
+   };
+   let side = up.cross(&view).normalize();
+   side * angle.sin() - view * angle.cos()
+}
+
+// Capas de cráteres de la luna: (zoom, desplazamiento en el noise, densidad).
+// Con la frecuencia base del noise (0.01) hace falta un zoom de ~40 para que el
+// noise supere el umbral sobre la esfera unitaria; por debajo no aparece ningún cráter
+fn crater_layers(params: &MoonParams) -> [(f32, f32, f32); 3] {
+   [
+      (40.0, 300.0, params.large_crater_density),
+      (100.0, 500.0, params.medium_crater_density),
+      (240.0, 700.0, params.small_crater_density),
+   ]
+}
+
+// Más densidad baja el umbral y hay más superficie cubierta por cráteres
+fn crater_threshold(density: f32) -> f32 {
+   1.0 - 0.3 * density
+}
+
+fn crater_noise_at(ctx: &FrameContext, position: nalgebra_glm::Vec3, crater_zoom: f32, crater_offset: f32) -> f32 {
+   ctx.noise.get_noise_3d(
+      position.x * crater_zoom + crater_offset,
+      position.y * crater_zoom,
+      position.z * crater_zoom,
+   )
+}
+
+// Celdas del id de cráter: un cuarto de la separación entre cráteres (frecuencia base
+// del noise 0.01 × 4), así cráteres vecinos nunca comparten celda
+const CRATER_ID_FREQUENCY: f32 = 0.04;
+
+// Centro del cráter donde cae `position`, estimado con un paso de Newton sobre el noise
+// (gradiente / curvatura en cada eje del plano tangente). None si ahí el noise no
+// forma una cima, por ejemplo en una pared entre dos cráteres.
+fn crater_center(ctx: &FrameContext, position: nalgebra_glm::Vec3, crater_zoom: f32, crater_offset: f32) -> Option<nalgebra_glm::Vec3> {
+   let sample = |p: nalgebra_glm::Vec3| crater_noise_at(ctx, p, crater_zoom, crater_offset);
+   let (tangent, bitangent) = tangent_basis(position.normalize());
+   let eps = 0.5 / crater_zoom;
+   let here = sample(position);
    
-   // Solo renderizar en las zonas de anillos (no muy cerca del planeta)
-   if distance_from_center < 1.1 || distance_from_center > 1.4 {
-      return Color::from_hex(0x000000); // Transparente fuera del rango de anillos
+   let mut center = position;
+   for axis in [tangent, bitangent] {
+      let ahead = sample(position + axis * eps);
+      let behind = sample(position - axis * eps);
+      let slope = (ahead - behind) / (2.0 * eps);
+      let curvature = (ahead + behind - 2.0 * here) / (eps * eps);
+      if curvature >= 0.0 {
+         return None;
+      }
+      center -= axis * (slope / curvature);
+   }
+   Some(center)
+}
+
+// Base del plano tangente a la esfera en `normal` (la misma convención en todo el shader)
+fn tangent_basis(normal: nalgebra_glm::Vec3) -> (nalgebra_glm::Vec3, nalgebra_glm::Vec3) {
+   let up = if normal.y.abs() < 0.99 {
+      nalgebra_glm::Vec3::new(0.0, 1.0, 0.0)
+   } else {
+      nalgebra_glm::Vec3::new(1.0, 0.0, 0.0)
+   };
+   let tangent = up.cross(&normal).normalize();
+   (tangent, normal.cross(&tangent))
+}
+
+// Ángulo alrededor del cráter más cercano, medido en el plano tangente.
+// El gradiente del noise de cráteres apunta hacia el centro del cráter, así que
+// todos los puntos sobre un mismo rayo radial comparten el mismo ángulo.
+fn crater_ray_angle(ctx: &FrameContext, position: nalgebra_glm::Vec3, crater_zoom: f32, crater_offset: f32) -> f32 {
+   let sample = |p: nalgebra_glm::Vec3| crater_noise_at(ctx, p, crater_zoom, crater_offset);
+   
+   let eps = 0.01;
+   let dx = nalgebra_glm::Vec3::new(eps, 0.0, 0.0);
+   let dy = nalgebra_glm::Vec3::new(0.0, eps, 0.0);
+   let dz = nalgebra_glm::Vec3::new(0.0, 0.0, eps);
+   let gradient = nalgebra_glm::Vec3::new(
+      sample(position + dx) - sample(position - dx),
+      sample(position + dy) - sample(position - dy),
+      sample(position + dz) - sample(position - dz),
+   );
+   
+   let (tangent, bitangent) = tangent_basis(position.normalize());
+   gradient.dot(&bitangent).atan2(gradient.dot(&tangent))
+}
+
+// ============================================
+// RINGS SHADER - Anillos con partículas de hielo y rocas
+// ============================================
+fn rings_shader(fragment: &Fragment, ctx: &FrameContext) -> Color {
+   let position = ctx.sample_position(fragment);
+   let time = ctx.time;
+   
+   // Calcular distancia desde el centro para crear anillos concéntricos
+   let distance_from_center = (position.x * position.x + position.z * position.z).sqrt();
+   
+   // Solo renderizar en las zonas de anillos (no muy cerca del planeta)
+   if !(RING_INNER_RADIUS..=RING_OUTER_RADIUS).contains(&distance_from_center) {
+      return Color::from_hex(0x000000); // Transparente fuera del rango de anillos
    }
    
    // Crear múltiples anillos con diferentes densidades
@@ -428,7 +1329,7 @@ fn rings_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    
    // Capa 1: Partículas de hielo (brillantes)
    let ice_zoom = 50.0;
-   let ice_noise = uniforms.noise.get_noise_3d(
+   let ice_noise = ctx.noise.get_noise_3d(
       position.x * ice_zoom + time * 0.1,
       position.y * ice_zoom,
       position.z * ice_zoom - time * 0.1,
@@ -436,7 +1337,7 @@ fn rings_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    
    // Capa 2: Rocas más grandes (oscuras)
    let rock_zoom = 20.0;
-   let rock_noise = uniforms.noise.get_noise_3d(
+   let rock_noise = ctx.noise.get_noise_3d(
       position.x * rock_zoom - time * 0.05,
       position.y * rock_zoom,
       position.z * rock_zoom + time * 0.05,
@@ -450,11 +1351,11 @@ fn rings_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    // Mezclar materiales basado en el noise
    let base_color = if ice_noise > 0.3 {
       // Partículas de hielo brillante
-      let ice_factor = (ice_noise - 0.3) / 0.7;
+      let ice_factor = remap(ice_noise, 0.3, 1.0, 0.0, 1.0);
       lerp_color(&dust_color, &ice_color, ice_factor)
    } else if rock_noise > 0.1 {
       // Rocas más oscuras
-      let rock_factor = (rock_noise - 0.1) / 0.9;
+      let rock_factor = remap(rock_noise, 0.1, 1.0, 0.0, 1.0);
       lerp_color(&dust_color, &rock_color, rock_factor)
    } else {
       // Polvo fino de fondo
@@ -463,13 +1364,17 @@ fn rings_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    
    // Capa 3: Variación de densidad en los anillos
    let density_zoom = 8.0;
-   let density_noise = uniforms.noise.get_noise_2d(
+   let density_noise = ctx.noise.get_noise_2d(
       distance_from_center * density_zoom,
       time * 0.02,
    );
    
-   let density_factor = (density_noise + 1.0) * 0.5;
+   // Capa 4: Ondas de densidad en espiral que giran lentamente
+   let params = &ctx.uniforms.params.rings;
+   let density_factor = (density_noise + 1.0) * 0.5 * ring_spiral_factor(ctx, position, distance_from_center);
+   
    let final_alpha = ring_pattern * density_factor;
+   let edge_fade = ring_edge_fade(distance_from_center, params.edge_width);
    
    // Aplicar transparencia basada en la densidad
    if final_alpha < 0.3 {
@@ -477,14 +1382,1658 @@ fn rings_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
    } else {
       // Mezclar con el color de fondo espacial para simular transparencia
       let space_color = Color::from_hex(0x000011);
-      blend_colors(&space_color, &base_color, final_alpha * 0.5) // Más transparente
+      blend_colors(&space_color, &base_color, final_alpha * 0.5 * edge_fade) // Más transparente
+   }
+}
+
+// Modulación de la densidad por las ondas en espiral (1 = sin cambio)
+fn ring_spiral_factor(ctx: &FrameContext, position: nalgebra_glm::Vec3, distance_from_center: f32) -> f32 {
+   let params = &ctx.uniforms.params.rings;
+   if params.spiral_strength <= 0.0 {
+      return 1.0;
+   }
+   let spiral_arms = 2.0;
+   let angle = position.z.atan2(position.x);
+   let spiral_phase = angle * spiral_arms
+      + distance_from_center * params.spiral_tightness * 2.0 * std::f32::consts::PI
+      - ctx.time * params.spiral_speed * 2.0 * std::f32::consts::PI;
+   1.0 + params.spiral_strength * spiral_phase.sin()
+}
+
+const RING_INNER_RADIUS: f32 = 1.1;
+const RING_OUTER_RADIUS: f32 = 1.4;
+
+// Opacidad relativa cerca de los bordes del anillo: sube con smoothstep desde el radio interior
+// y baja igual hacia el exterior, así el anillo se funde con el hueco y el espacio
+fn ring_edge_fade(distance_from_center: f32, edge_width: f32) -> f32 {
+   if edge_width <= 0.0 {
+      return 1.0;
    }
+   // Nunca más ancho que medio anillo, para que las dos rampas no se pisen
+   let width = edge_width.min((RING_OUTER_RADIUS - RING_INNER_RADIUS) * 0.5);
+   let inner = smoothstep(RING_INNER_RADIUS, RING_INNER_RADIUS + width, distance_from_center);
+   let outer = 1.0 - smoothstep(RING_OUTER_RADIUS - width, RING_OUTER_RADIUS, distance_from_center);
+   inner * outer
 }
 
 // ============================================
 // STARFIELD SHADER - Campo de estrellas simple
 // ============================================
-fn starfield_shader(_fragment: &Fragment, _uniforms: &Uniforms) -> Color {
+fn starfield_shader(_fragment: &Fragment, _ctx: &FrameContext) -> Color {
    // Temporalmente devolver solo fondo negro transparente para debug
    Color::from_hex(0x000000)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::params::{DustStormLayer, GasGiantParams, RockyPlanetParams, StormSpot};
+   use nalgebra_glm::{Mat4, Vec3};
+   use std::cell::Cell;
+
+   thread_local! {
+      // Veces que un fragmento entró en una capa fina (ver detail_layer_enabled)
+      pub(super) static DETAIL_LAYER_SAMPLES: Cell<usize> = const { Cell::new(0) };
+   }
+
+   fn test_uniforms() -> Uniforms {
+      Uniforms::new(Mat4::identity(), Mat4::identity(), Mat4::identity(), Mat4::identity(), 0.0)
+   }
+
+   // Fragmento sobre la esfera unitaria con la normal de la esfera
+   fn sphere_fragment(position: Vec3, intensity: f32) -> Fragment {
+      let position = position.normalize();
+      Fragment::default()
+         .with_vertex_position(position)
+         .with_world_position(position)
+         .with_normal(position)
+         .with_intensity(intensity)
+   }
+
+   // Puntos repartidos de forma pareja sobre la esfera unitaria (espiral de Fibonacci)
+   fn sphere_points(count: usize) -> Vec<Vec3> {
+      let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+      (0..count)
+         .map(|i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let ring = (1.0 - y * y).sqrt();
+            let angle = golden_angle * i as f32;
+            Vec3::new(ring * angle.cos(), y, ring * angle.sin())
+         })
+         .collect()
+   }
+
+   // Lo que suma la especular del planeta rocoso sobre el mismo fragmento sin ella, por canal.
+   // Sin difusa, para que la superficie quede oscura y el reflejo no sature
+   fn specular_gain(ctx: &FrameContext, point: Vec3) -> [i32; 3] {
+      let fragment = sphere_fragment(point, 0.0);
+      let matte = LightingResult { diffuse: 0.0, specular: 0.0, ambient: 1.0 };
+      let shiny = LightingResult { specular: 1.0, ..matte };
+      let (with, without) = (
+         fragment_shader_lit(&fragment, ctx, &ShaderType::RockyPlanet, shiny).to_hex(),
+         fragment_shader_lit(&fragment, ctx, &ShaderType::RockyPlanet, matte).to_hex(),
+      );
+      [16, 8, 0].map(|shift| ((with >> shift) & 0xff) as i32 - ((without >> shift) & 0xff) as i32)
+   }
+
+   #[test]
+   fn every_crater_layer_crosses_its_threshold() {
+      let uniforms = test_uniforms();
+      let ctx = FrameContext::new(&uniforms);
+      let points = sphere_points(20_000);
+      let step = 0.02;
+
+      let mut coherence = Vec::new();
+      for (zoom, offset, density) in crater_layers(&MoonParams::default()) {
+         let threshold = crater_threshold(density);
+         let in_crater = |p: Vec3| crater_noise_at(&ctx, p, zoom, offset) > threshold;
+         let hits: Vec<Vec3> = points.iter().copied().filter(|p| in_crater(*p)).collect();
+         assert!(!hits.is_empty(), "zoom {zoom}: no craters on the sphere");
+
+         // Qué fracción de los puntos en un cráter sigue en él un paso más allá:
+         // los cráteres grandes se ven con un muestreo grueso, los chicos no
+         let neighbours = hits.iter().filter(|p| in_crater(**p + Vec3::new(step, 0.0, 0.0))).count();
+         coherence.push(neighbours as f32 / hits.len() as f32);
+      }
+
+      assert!(coherence[0] > coherence[1] && coherence[1] > coherence[2], "{coherence:?}");
+   }
+
+   #[test]
+   fn large_and_small_craters_both_darken_the_moon() {
+      let shade_with = |large: f32, small: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.moon.large_crater_density = large;
+         uniforms.params.moon.medium_crater_density = 0.0;
+         uniforms.params.moon.small_crater_density = small;
+         uniforms.params.moon.ejecta_rays = false;
+         uniforms.params.moon.crater_rim_brightness = 0.0;
+         let ctx = FrameContext::new(&uniforms);
+         sphere_points(4_000)
+            .into_iter()
+            .map(|p| fragment_shader(&sphere_fragment(p, 1.0), &ctx, &ShaderType::Moon).luminance())
+            .collect::<Vec<f32>>()
+      };
+
+      let bare = shade_with(0.0, 0.0);
+      let darkened = |shaded: &[f32]| shaded.iter().zip(&bare).filter(|(a, b)| *a < *b).count();
+      assert!(darkened(&shade_with(0.6, 0.0)) > 0);
+      assert!(darkened(&shade_with(0.0, 0.8)) > 0);
+   }
+
+   // Cima del cráter más marcado de una capa, refinada con el mismo paso de Newton del shader
+   fn deepest_crater(ctx: &FrameContext, zoom: f32, offset: f32) -> Vec3 {
+      let noise_at = |p: Vec3| crater_noise_at(ctx, p, zoom, offset);
+      let mut center = sphere_points(20_000)
+         .into_iter()
+         .max_by(|a, b| noise_at(*a).total_cmp(&noise_at(*b)))
+         .unwrap();
+      for _ in 0..4 {
+         if let Some(refined) = crater_center(ctx, center, zoom, offset) {
+            center = refined.normalize();
+         }
+      }
+      center
+   }
+
+   // Punto del fondo: donde el noise cae a mitad de camino entre la cima y el umbral
+   fn crater_floor(ctx: &FrameContext, center: Vec3, zoom: f32, offset: f32, threshold: f32) -> Vec3 {
+      let noise_at = |p: Vec3| crater_noise_at(ctx, p, zoom, offset);
+      let target = threshold + (noise_at(center) - threshold) * 0.5;
+      let (tangent, _) = tangent_basis(center);
+      let mut point = center;
+      while noise_at(point) > target {
+         point = (point + tangent * 0.001).normalize();
+      }
+      point
+   }
+
+   // Luna con una sola capa de cráteres, sin bordes, rayos ni detalle que ensucien la medición
+   fn single_layer_moon(layer: usize) -> Uniforms {
+      let mut uniforms = test_uniforms();
+      let moon = &mut uniforms.params.moon;
+      let densities = [moon.large_crater_density, moon.medium_crater_density, moon.small_crater_density];
+      moon.large_crater_density = if layer == 0 { densities[0] } else { 0.0 };
+      moon.medium_crater_density = if layer == 1 { densities[1] } else { 0.0 };
+      moon.small_crater_density = if layer == 2 { densities[2] } else { 0.0 };
+      moon.crater_rim_brightness = 0.0;
+      moon.ejecta_rays = false;
+      moon.detail_blend = 0.0;
+      uniforms
+   }
+
+   #[test]
+   fn central_peak_only_rises_in_large_craters() {
+      for layer in [0, 2] {
+         let mut uniforms = single_layer_moon(layer);
+         uniforms.params.moon.central_peak_probability = 1.0;
+         let ctx = FrameContext::new(&uniforms);
+         let (zoom, offset, density) = crater_layers(&uniforms.params.moon)[layer];
+
+         let center = deepest_crater(&ctx, zoom, offset);
+         let floor = crater_floor(&ctx, center, zoom, offset, crater_threshold(density));
+         let brightness = |p: Vec3| fragment_shader(&sphere_fragment(p, 1.0), &ctx, &ShaderType::Moon).luminance();
+
+         if layer == 0 {
+            assert!(brightness(center) > brightness(floor), "large crater has no central peak");
+         } else {
+            assert!(brightness(center) <= brightness(floor), "small crater got a central peak");
+         }
+      }
+   }
+
+   #[test]
+   fn crater_peak_decision_is_shared_by_the_whole_peak() {
+      let mut uniforms = single_layer_moon(0);
+      uniforms.params.moon.central_peak_probability = 1.0;
+      let ctx = FrameContext::new(&uniforms);
+      let (zoom, offset, _) = crater_layers(&uniforms.params.moon)[0];
+      let center = deepest_crater(&ctx, zoom, offset);
+
+      // Alrededor de la cima todos los puntos estiman el mismo centro (y por lo tanto la misma celda)
+      let (tangent, bitangent) = tangent_basis(center);
+      let reference = crater_center(&ctx, center, zoom, offset).unwrap() * zoom * CRATER_ID_FREQUENCY;
+      let reference_id = ctx.cell_noise.get_noise_3d(reference.x, reference.y, reference.z);
+      for (du, dv) in [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)] {
+         let nearby = (center + (tangent * du + bitangent * dv) * (0.8 / zoom)).normalize();
+         let estimate = crater_center(&ctx, nearby, zoom, offset).unwrap() * zoom * CRATER_ID_FREQUENCY;
+         assert_eq!(ctx.cell_noise.get_noise_3d(estimate.x, estimate.y, estimate.z), reference_id);
+      }
+   }
+
+   #[test]
+   fn ejecta_rays_brighten_along_rays_only() {
+      let moon_with_rays = |rays: bool| {
+         let mut uniforms = single_layer_moon(0);
+         uniforms.params.moon.ejecta_rays = rays;
+         uniforms
+      };
+      let (with_rays, without_rays) = (moon_with_rays(true), moon_with_rays(false));
+      let (ctx_rays, ctx_plain) = (FrameContext::new(&with_rays), FrameContext::new(&without_rays));
+      let (zoom, offset, density) = crater_layers(&with_rays.params.moon)[0];
+      let threshold = crater_threshold(density);
+      let center = deepest_crater(&ctx_rays, zoom, offset);
+      let (tangent, bitangent) = tangent_basis(center);
+
+      // Recorre un anillo justo afuera del borde, en varias direcciones alrededor del cráter
+      let (mut brightened, mut unchanged) = (0, 0);
+      for step in 0..72 {
+         let angle = step as f32 / 72.0 * std::f32::consts::TAU;
+         let direction = tangent * angle.cos() + bitangent * angle.sin();
+         let mut point = center;
+         while crater_noise_at(&ctx_rays, point, zoom, offset) > threshold - 0.05 {
+            point = (point + direction * 0.002).normalize();
+         }
+
+         let fragment = sphere_fragment(point, 1.0);
+         let lit = fragment_shader(&fragment, &ctx_rays, &ShaderType::Moon).luminance();
+         let plain = fragment_shader(&fragment, &ctx_plain, &ShaderType::Moon).luminance();
+         assert!(lit >= plain);
+         if lit > plain {
+            brightened += 1;
+         } else {
+            unchanged += 1;
+         }
+      }
+
+      assert!(brightened > 0, "no rays around the crater");
+      assert!(unchanged > 0, "the whole ring is brightened, not separate rays");
+   }
+
+   #[test]
+   fn reveal_mask_hides_everything_in_the_masked_region() {
+      // Luz sobre el polo del hemisferio oculto: hielo y océano con brillo especular
+      let lit_from_pole = || {
+         let mut uniforms = test_uniforms();
+         uniforms.light_direction = Vec3::new(-0.3, 0.9, 0.3).normalize();
+         uniforms.params.rocky.sea_level = Some(0.0);
+         uniforms
+      };
+      let (revealed, mut masked) = (lit_from_pole(), lit_from_pole());
+      masked.reveal_mask = Some(Arc::new(|p: Vec3| if p.x < 0.0 { 0.0 } else { 1.0 }));
+      let (ctx_revealed, ctx_masked) = (FrameContext::new(&revealed), FrameContext::new(&masked));
+
+      for shader in [ShaderType::RockyPlanet, ShaderType::GasGiant, ShaderType::Moon] {
+         for point in sphere_points(2000) {
+            let fragment = sphere_fragment(point, point.normalize().dot(&revealed.light_direction).max(0.0));
+            let color = fragment_shader(&fragment, &ctx_masked, &shader);
+            if point.x < 0.0 {
+               assert!(color.luminance() < 0.01, "{shader:?} at {point:?}: {color:?}");
+            } else {
+               assert_eq!(color, fragment_shader(&fragment, &ctx_revealed, &shader));
+            }
+         }
+      }
+   }
+
+   #[test]
+   fn constant_ocean_provider_covers_the_whole_planet() {
+      let ocean_world = |seed: i32| {
+         let mut uniforms = test_uniforms();
+         uniforms.seed = seed;
+         uniforms.terrain_provider = Some(Arc::new(|_| (-0.5, Biome::Ocean)));
+         uniforms
+      };
+      let (first, second) = (ocean_world(1337), ocean_world(42));
+      let (ctx_first, ctx_second) = (FrameContext::new(&first), FrameContext::new(&second));
+
+      // Polos incluidos: el mapa manda sobre los casquetes procedurales.
+      // Sin especular, para que el reflejo del sol no tape el color del agua
+      let light = LightingResult { diffuse: 1.0, specular: 0.0, ambient: 1.0 };
+      for point in sphere_points(2000) {
+         let fragment = sphere_fragment(point, 1.0);
+         let color = fragment_shader_lit(&fragment, &ctx_first, &ShaderType::RockyPlanet, light);
+         let (r, g, b) = ((color.to_hex() >> 16) & 0xff, (color.to_hex() >> 8) & 0xff, color.to_hex() & 0xff);
+         assert!(b > r && b > g, "not ocean at {point:?}: {color:?}");
+         assert_eq!(color, fragment_shader_lit(&fragment, &ctx_second, &ShaderType::RockyPlanet, light), "depends on the noise seed");
+      }
+   }
+
+   #[test]
+   fn red_light_tints_a_gray_moon_and_keeps_its_shading() {
+      let lit_by = |light_color: Color| {
+         let mut uniforms = test_uniforms();
+         uniforms.light_color = light_color;
+         uniforms
+      };
+      let (white, red) = (lit_by(Color::new(255, 255, 255)), lit_by(Color::new(255, 60, 60)));
+      let (ctx_white, ctx_red) = (FrameContext::new(&white), FrameContext::new(&red));
+      let channels = |color: Color| {
+         let hex = color.to_hex();
+         ((hex >> 16) & 0xff, (hex >> 8) & 0xff, hex & 0xff)
+      };
+
+      let point = Vec3::new(0.3, 0.2, 1.0);
+      let (bright, dim) = (sphere_fragment(point, 1.0), sphere_fragment(point, 0.4));
+      let white_bright = fragment_shader(&bright, &ctx_white, &ShaderType::Moon);
+      let (r, g, b) = channels(white_bright);
+      assert!(r.abs_diff(g) <= 2 && g.abs_diff(b) <= 2, "the moon is not gray: {white_bright:?}");
+
+      let red_bright = fragment_shader(&bright, &ctx_red, &ShaderType::Moon);
+      let red_dim = fragment_shader(&dim, &ctx_red, &ShaderType::Moon);
+      for color in [red_bright, red_dim] {
+         let (r, g, b) = channels(color);
+         assert!(r > g + 10 && r > b + 10, "not reddish: {color:?}");
+      }
+      assert!(red_bright.luminance() > red_dim.luminance());
+      assert!(channels(red_bright).0 >= channels(red_dim).0);
+      assert!(fragment_shader(&dim, &ctx_white, &ShaderType::Moon).luminance() < white_bright.luminance());
+   }
+
+   #[test]
+   fn ocean_glint_follows_the_light_color_near_the_reflection() {
+      let planet = |sea_level: Option<f32>| {
+         let mut uniforms = test_uniforms();
+         uniforms.light_color = Color::new(255, 60, 60);
+         uniforms.params.rocky.sea_level = sea_level;
+         uniforms
+      };
+      // Con el nivel del mar por encima de todo el noise, el planeta es un océano
+      let (ocean, land) = (planet(Some(2.0)), planet(None));
+      let (ctx_ocean, ctx_land) = (FrameContext::new(&ocean), FrameContext::new(&land));
+
+      // Luz y cámara sobre +z: el reflejo cae en el centro del disco
+      let [r, g, b] = specular_gain(&ctx_ocean, Vec3::new(0.0, 0.0, 1.0));
+      assert!(r > 100 && r > 2 * g && r > 2 * b, "glint is not reddish: {:?}", (r, g, b));
+      assert_eq!(specular_gain(&ctx_ocean, Vec3::new(1.0, 0.0, 0.3)), [0, 0, 0]);
+      assert_eq!(specular_gain(&ctx_land, Vec3::new(0.0, 0.0, 1.0)), [0, 0, 0]);
+   }
+
+   #[test]
+   fn ice_glints_toward_the_sun_and_land_does_not() {
+      // Luz reflejada por la cámara (+z) justo en este punto del casquete norte
+      let glint_point = Vec3::new(0.0, 0.95, 0.3).normalize();
+      let view = Vec3::new(0.0, 0.0, 1.0);
+      let mut polar_sun = test_uniforms();
+      polar_sun.light_direction = glint_point * (2.0 * glint_point.dot(&view)) - view;
+      polar_sun.params.rocky.polar_color = Color::from_hex(0x404040);
+      let front_sun = test_uniforms();
+      let (ctx_polar, ctx_front) = (FrameContext::new(&polar_sun), FrameContext::new(&front_sun));
+
+      let facing = specular_gain(&ctx_polar, glint_point);
+      let off_angle = specular_gain(&ctx_polar, Vec3::new(0.4, 0.95, -0.1));
+      assert!(facing.iter().all(|gain| *gain > 100), "no glint: {facing:?}");
+      assert!(off_angle.iter().sum::<i32>() < facing.iter().sum::<i32>() / 10);
+
+      // Tierra seca en el ecuador, con la luz justo en el ángulo de reflejo
+      assert_eq!(specular_gain(&ctx_front, Vec3::new(0.0, 0.0, 1.0)), [0, 0, 0]);
+   }
+
+   #[test]
+   fn moon_phase_lights_full_darkens_new_and_splits_quarters() {
+      let at_phase = |phase: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.moon.phase = Some(phase);
+         uniforms
+      };
+      // Disco visible desde la cámara (+z), sin el limbo rasante
+      let disk: Vec<Vec3> = sphere_points(2000).into_iter().filter(|p| p.z > 0.2).collect();
+      // La intensidad real de la escena no cuenta: la fase la reemplaza
+      let brightness = |uniforms: &Uniforms, point: Vec3| {
+         fragment_shader(&sphere_fragment(point, 1.0), &FrameContext::new(uniforms), &ShaderType::Moon).luminance()
+      };
+
+      let full = at_phase(0.5);
+      assert!(disk.iter().all(|p| brightness(&full, *p) > 0.04));
+      for new in [at_phase(0.0), at_phase(1.0)] {
+         assert!(disk.iter().all(|p| brightness(&new, *p) < 0.025));
+      }
+
+      // Cuarto creciente: la luz llega de +x; cuarto menguante, de -x
+      for (phase, lit_side) in [(0.25, 1.0), (0.75, -1.0)] {
+         let quarter = at_phase(phase);
+         for point in &disk {
+            let side = point.x * lit_side;
+            if side > 0.3 {
+               assert!(brightness(&quarter, *point) > 0.04, "phase {phase}: dark at {point:?}");
+            } else if side < -0.02 {
+               assert!(brightness(&quarter, *point) < 0.025, "phase {phase}: lit at {point:?}");
+            }
+         }
+      }
+   }
+
+   #[test]
+   fn halving_the_rotation_period_doubles_the_surface_advance() {
+      let frame = |time: f32, rotation_period: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.rotation_period = rotation_period;
+         // Tormentas densas para que las nubes aparezcan en la muestra
+         uniforms.params.rocky.storm_layers = vec![DustStormLayer { coverage: 0.9, ..DustStormLayer::default() }];
+         uniforms
+      };
+      let advance = |rotation_period: f32| {
+         FrameContext::new(&frame(1.1, rotation_period)).surface_time - FrameContext::new(&frame(1.0, rotation_period)).surface_time
+      };
+      assert!((advance(0.5) / advance(1.0) - 2.0).abs() < 0.01);
+
+      // Nubes (tormentas del planeta rocoso) y turbulencia del gigante leen el mismo tiempo
+      // de superficie: medio día a tiempo t se ve igual que un día entero a tiempo 2t,
+      // y distinto de un día entero a tiempo t
+      let (short_day, long_day, same_time) = (frame(15.0, 0.5), frame(30.0, 1.0), frame(15.0, 1.0));
+      let (ctx_short, ctx_long, ctx_same) = (FrameContext::new(&short_day), FrameContext::new(&long_day), FrameContext::new(&same_time));
+      for shader in [ShaderType::RockyPlanet, ShaderType::GasGiant] {
+         let mut moved = 0;
+         for point in sphere_points(500) {
+            let fragment = sphere_fragment(point, 1.0);
+            let short = fragment_shader(&fragment, &ctx_short, &shader);
+            assert_eq!(short, fragment_shader(&fragment, &ctx_long, &shader), "{shader:?}");
+            if short != fragment_shader(&fragment, &ctx_same, &shader) {
+               moved += 1;
+            }
+         }
+         assert!(moved > 20, "{shader:?} barely animates: {moved}");
+      }
+
+      for rotation_period in [0.0, -1.0] {
+         assert!(FrameContext::new(&frame(1.0, rotation_period)).surface_time.is_finite());
+      }
+   }
+
+   #[test]
+   fn zero_cloud_blend_removes_the_storms_only() {
+      let rocky = |cloud_blend: f32, storms: bool| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.cloud_blend = cloud_blend;
+         uniforms.params.rocky.storm_layers = if storms {
+            vec![DustStormLayer { coverage: 0.9, ..DustStormLayer::default() }]
+         } else {
+            Vec::new()
+         };
+         uniforms
+      };
+      let (hidden, clear, cloudy) = (rocky(0.0, true), rocky(0.3, false), rocky(0.3, true));
+      let (ctx_hidden, ctx_clear, ctx_cloudy) = (FrameContext::new(&hidden), FrameContext::new(&clear), FrameContext::new(&cloudy));
+
+      let mut cloud_fragments = 0;
+      for point in sphere_points(2000) {
+         let fragment = sphere_fragment(point, 0.8);
+         let without_clouds = fragment_shader(&fragment, &ctx_clear, &ShaderType::RockyPlanet);
+         assert_eq!(fragment_shader(&fragment, &ctx_hidden, &ShaderType::RockyPlanet), without_clouds);
+         if fragment_shader(&fragment, &ctx_cloudy, &ShaderType::RockyPlanet) != without_clouds {
+            cloud_fragments += 1;
+         }
+      }
+      assert!(cloud_fragments > 100, "the storms never show: {cloud_fragments}");
+   }
+
+   #[test]
+   fn ring_fades_toward_both_edges() {
+      let width = 0.08;
+      let middle = (RING_INNER_RADIUS + RING_OUTER_RADIUS) * 0.5;
+      assert!(ring_edge_fade(RING_INNER_RADIUS + 0.01, width) < ring_edge_fade(middle, width));
+      assert!(ring_edge_fade(RING_OUTER_RADIUS - 0.01, width) < ring_edge_fade(middle, width));
+      assert_eq!(ring_edge_fade(middle, width), 1.0);
+      assert_eq!(ring_edge_fade(RING_INNER_RADIUS + 0.01, 0.0), 1.0);
+
+      // En el shader: con el desvanecido los bordes quedan más cerca del espacio y el centro no cambia
+      let rings = |edge_width: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rings.edge_width = edge_width;
+         uniforms
+      };
+      let (faded, sharp) = (rings(width), rings(0.0));
+      let (ctx_faded, ctx_sharp) = (FrameContext::new(&faded), FrameContext::new(&sharp));
+      let (mut dimmer_inner, mut dimmer_outer) = (0, 0);
+      for step in 0..60 {
+         let radius = RING_INNER_RADIUS + (RING_OUTER_RADIUS - RING_INNER_RADIUS) * (step as f32 + 0.5) / 60.0;
+         for turn in 0..24 {
+            let angle = turn as f32 / 24.0 * std::f32::consts::TAU;
+            let position = Vec3::new(radius * angle.cos(), 0.0, radius * angle.sin());
+            let fragment = Fragment::default().with_vertex_position(position).with_world_position(position);
+            let with_fade = fragment_shader(&fragment, &ctx_faded, &ShaderType::RingedPlanet).luminance();
+            let without_fade = fragment_shader(&fragment, &ctx_sharp, &ShaderType::RingedPlanet).luminance();
+
+            if radius < RING_INNER_RADIUS + width || radius > RING_OUTER_RADIUS - width {
+               assert!(with_fade <= without_fade);
+               if with_fade < without_fade {
+                  if radius < middle { dimmer_inner += 1 } else { dimmer_outer += 1 }
+               }
+            } else {
+               assert_eq!(with_fade, without_fade);
+            }
+         }
+      }
+      assert!(dimmer_inner > 0 && dimmer_outer > 0, "inner {dimmer_inner}, outer {dimmer_outer}");
+   }
+
+   #[test]
+   fn custom_shader_slots_dispatch_by_index() {
+      let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+      let counter = calls.clone();
+      let mut uniforms = test_uniforms();
+      uniforms.custom_shaders.push(Arc::new(move |fragment: &Fragment, _: &FrameContext, light: &LightingResult| {
+         counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+         Color::from_float(light.diffuse, fragment.vertex_position.y, 0.5)
+      }));
+      let ctx = FrameContext::new(&uniforms);
+      let fragment = sphere_fragment(Vec3::new(0.0, 1.0, 0.0), 0.2);
+
+      assert_eq!(fragment_shader(&fragment, &ctx, &ShaderType::Custom(0)), Color::from_float(0.2, 1.0, 0.5));
+      assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+      // Índice sin shader registrado: negro, sin entrar en pánico
+      assert_eq!(fragment_shader(&fragment, &ctx, &ShaderType::Custom(1)), Color::black());
+      assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+   }
+
+   #[test]
+   fn soft_polar_edge_has_no_hard_line() {
+      let caps = |soft: bool| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.soft_polar_edge = soft;
+         uniforms
+      };
+      // Mayor salto de brillo entre muestras vecinas a lo largo de un meridiano que cruza el borde
+      let largest_step = |uniforms: &Uniforms| {
+         let ctx = FrameContext::new(uniforms);
+         let samples: Vec<f32> = (0..400)
+            .map(|i| {
+               let latitude = 0.7 + 0.29 * i as f32 / 400.0;
+               let point = Vec3::new((1.0 - latitude * latitude).sqrt(), latitude, 0.0);
+               fragment_shader(&sphere_fragment(point, 1.0), &ctx, &ShaderType::RockyPlanet).luminance()
+            })
+            .collect();
+         samples.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max)
+      };
+
+      let (soft, hard) = (largest_step(&caps(true)), largest_step(&caps(false)));
+      assert!(hard > 0.2, "the hard edge should jump: {hard}");
+      assert!(soft < hard * 0.25, "soft {soft} vs hard {hard}");
+   }
+
+   #[test]
+   fn time_scale_freezes_and_speeds_up_animation() {
+      let frame = |time: f32, time_scale: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.time_scale = time_scale;
+         uniforms
+      };
+      let render = |uniforms: &Uniforms, shader: &ShaderType| {
+         let ctx = FrameContext::new(uniforms);
+         sphere_points(500)
+            .into_iter()
+            .map(|point| fragment_shader(&sphere_fragment(point, 1.0), &ctx, shader))
+            .collect::<Vec<_>>()
+      };
+
+      for shader in [ShaderType::Sun, ShaderType::GasGiant] {
+         // El shader sí se anima con tiempo real
+         assert_ne!(render(&frame(2.0, 1.0), &shader), render(&frame(20.0, 1.0), &shader), "{shader:?}");
+         assert_eq!(render(&frame(2.0, 0.0), &shader), render(&frame(20.0, 0.0), &shader), "{shader:?}");
+         assert_eq!(render(&frame(3.0, 2.0), &shader), render(&frame(6.0, 1.0), &shader), "{shader:?}");
+      }
+   }
+
+   #[test]
+   fn dust_warms_the_disk_and_more_at_the_limb() {
+      // Superficie gris neutra (todo hielo gris) para que el tinte del polvo se note solo
+      let dusty = |density: f32, dust_color: Color| {
+         let mut uniforms = test_uniforms();
+         uniforms.terrain_provider = Some(Arc::new(|_| (0.0, Biome::Ice)));
+         uniforms.params.rocky.polar_color = Color::from_hex(0x808080);
+         uniforms.params.rocky.dust_density = density;
+         uniforms.params.rocky.dust_color = dust_color;
+         uniforms
+      };
+      let (clear, dust, disabled) = (
+         dusty(0.0, Color::from_hex(0xe0a060)),
+         dusty(0.5, Color::from_hex(0xe0a060)),
+         dusty(0.0, Color::from_hex(0x00ff00)),
+      );
+      let (ctx_clear, ctx_dust, ctx_disabled) = (FrameContext::new(&clear), FrameContext::new(&dust), FrameContext::new(&disabled));
+      // Calidez: cuánto domina el rojo sobre el azul, en promedio
+      let warmth = |ctx: &FrameContext, points: &[Vec3]| {
+         points.iter().map(|point| {
+            let hex = fragment_shader(&sphere_fragment(*point, 0.8), ctx, &ShaderType::RockyPlanet).to_hex();
+            ((hex >> 16) & 0xff) as f32 - (hex & 0xff) as f32
+         }).sum::<f32>() / points.len() as f32
+      };
+
+      let visible: Vec<Vec3> = sphere_points(4000).into_iter().filter(|p| p.z > 0.0).collect();
+      let center: Vec<Vec3> = visible.iter().copied().filter(|p| p.z > 0.9).collect();
+      let limb: Vec<Vec3> = visible.iter().copied().filter(|p| p.z < 0.3).collect();
+      let center_gain = warmth(&ctx_dust, &center) - warmth(&ctx_clear, &center);
+      let limb_gain = warmth(&ctx_dust, &limb) - warmth(&ctx_clear, &limb);
+      assert!(center_gain > 0.0, "center {center_gain}");
+      assert!(limb_gain > center_gain, "limb {limb_gain} vs center {center_gain}");
+
+      for point in &visible {
+         let fragment = sphere_fragment(*point, 0.8);
+         assert_eq!(
+            fragment_shader(&fragment, &ctx_disabled, &ShaderType::RockyPlanet),
+            fragment_shader(&fragment, &ctx_clear, &ShaderType::RockyPlanet),
+         );
+      }
+   }
+
+   #[test]
+   fn oval_spot_is_wider_than_tall() {
+      let giant = |spot_blend: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.gas_giant.storm_radius_x = 0.4;
+         uniforms.params.gas_giant.storm_radius_y = 0.15;
+         uniforms.params.gas_giant.great_spot_blend = spot_blend;
+         uniforms.params.gas_giant.detail_blend = 0.0;
+         uniforms
+      };
+      let (with_spot, without_spot) = (giant(0.7), giant(0.0));
+      let (ctx_spot, ctx_plain) = (FrameContext::new(&with_spot), FrameContext::new(&without_spot));
+      let covered = |x: f32, y: f32| {
+         let point = Vec3::new(x, y, (1.0 - x * x - y * y).sqrt());
+         let fragment = sphere_fragment(point, 1.0);
+         fragment_shader(&fragment, &ctx_spot, &ShaderType::GasGiant) != fragment_shader(&fragment, &ctx_plain, &ShaderType::GasGiant)
+      };
+
+      // Alcance de la mancha desde su centro (0.3, 0.2) a lo largo de cada eje
+      let offsets: Vec<f32> = (-50..=50).map(|i| i as f32 * 0.01).collect();
+      let reach = |along_x: bool| {
+         offsets
+            .iter()
+            .filter(|offset| if along_x { covered(0.3 + **offset, 0.2) } else { covered(0.3, 0.2 + **offset) })
+            .fold(0.0f32, |reach, offset| reach.max(offset.abs()))
+      };
+      let (reach_x, reach_y) = (reach(true), reach(false));
+      assert!(reach_y > 0.0);
+      assert!(reach_x > reach_y * 1.5, "x {reach_x}, y {reach_y}");
+      assert!(reach_x <= 0.4 && reach_y <= 0.15);
+   }
+
+   #[test]
+   fn update_params_changes_output_but_not_matrices_or_time() {
+      let mut uniforms = test_uniforms();
+      uniforms.time = 4.0;
+      uniforms.model_matrix = nalgebra_glm::translate(&Mat4::identity(), &Vec3::new(1.0, 2.0, 3.0));
+      let fragment = sphere_fragment(Vec3::new(0.2, 0.3, 0.9), 0.7);
+      let before = fragment_shader(&fragment, &FrameContext::new(&uniforms), &ShaderType::Moon);
+
+      let mut params = ShaderParams::default();
+      params.moon.rim_strength = 1.0;
+      params.moon.rim_color = Color::from_hex(0xff0000);
+      uniforms.update_params(params);
+
+      let after = fragment_shader(&fragment, &FrameContext::new(&uniforms), &ShaderType::Moon);
+      assert_ne!(before, after);
+      assert_eq!(uniforms.time, 4.0);
+      assert_eq!(uniforms.model_matrix, nalgebra_glm::translate(&Mat4::identity(), &Vec3::new(1.0, 2.0, 3.0)));
+      assert_eq!(uniforms.view_matrix, Mat4::identity());
+   }
+
+   #[test]
+   fn granulation_flickers_per_cell_with_a_steady_average() {
+      let at_time = |time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms
+      };
+      let (early, late) = (at_time(1.0), at_time(2.3));
+      let (ctx_early, ctx_late) = (FrameContext::new(&early), FrameContext::new(&late));
+      let points = sphere_points(3000);
+      let brightness = |ctx: &FrameContext| -> Vec<f32> {
+         points.iter().map(|point| fragment_shader(&sphere_fragment(*point, 1.0), ctx, &ShaderType::Sun).luminance()).collect()
+      };
+      let (early, late) = (brightness(&ctx_early), brightness(&ctx_late));
+
+      let flickered = early.iter().zip(&late).filter(|(a, b)| (*a - *b).abs() > 0.03).count();
+      assert!(flickered > points.len() / 4, "only {flickered} fragments changed");
+      let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+      assert!((mean(&early) - mean(&late)).abs() < mean(&early) * 0.02, "{} vs {}", mean(&early), mean(&late));
+   }
+
+   #[test]
+   fn water_goes_shallow_to_deep_and_blends_into_land() {
+      // Un punto fijo del ecuador: moviendo el nivel del mar se recorre la profundidad en ese punto
+      let point = Vec3::new(0.6, 0.0, 0.8);
+      let terrain_height = {
+         let uniforms = test_uniforms();
+         let ctx = FrameContext::new(&uniforms);
+         ctx.noise.get_noise_3d(point.x * 4.0, point.y * 4.0, point.z * 4.0)
+      };
+      let at_depth = |depth: f32, deep_color: Option<Color>| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.sea_level = Some(terrain_height + depth);
+         if let Some(color) = deep_color {
+            uniforms.params.rocky.deep_water_color = color;
+         }
+         let ctx = FrameContext::new(&uniforms);
+         fragment_shader(&sphere_fragment(point, 0.8), &ctx, &ShaderType::RockyPlanet).to_hex()
+      };
+      let channels = |hex: u32| [(hex >> 16) & 0xff, (hex >> 8) & 0xff, hex & 0xff];
+      let close = |a: u32, b: u32, tolerance: u32| channels(a).iter().zip(channels(b)).all(|(x, y)| x.abs_diff(y) <= tolerance);
+
+      // Justo bajo el nivel del mar el agua es la somera; mar adentro, la profunda
+      let shallow = RockyPlanetParams::default().shallow_water_color;
+      assert!(close(at_depth(0.03, None), at_depth(0.03, Some(shallow)), 8));
+      assert!(!close(at_depth(0.03, None), at_depth(0.5, None), 20));
+
+      // Sin saltos: de tierra firme a mar profundo en pasos chicos, con la costa
+      // (coastline_width = 0.04, ocho pasos) repartida en varios colores intermedios
+      let sweep: Vec<u32> = (-20..=80).map(|step| at_depth(step as f32 * 0.005, None)).collect();
+      assert!(sweep.windows(2).all(|pair| close(pair[0], pair[1], 32)));
+      let mut coast = sweep[16..=24].to_vec();
+      coast.dedup();
+      assert!(coast.len() >= 6, "abrupt coastline: {coast:x?}");
+      assert_ne!(sweep[0], sweep[100]);
+   }
+
+   #[test]
+   fn rim_light_tints_the_limb_only() {
+      let rim_moon = |strength: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.moon.rim_strength = strength;
+         uniforms.params.moon.rim_color = Color::from_hex(0x4080ff);
+         uniforms
+      };
+      let (with_rim, without_rim) = (rim_moon(0.8), rim_moon(0.0));
+      let (ctx_rim, ctx_plain) = (FrameContext::new(&with_rim), FrameContext::new(&without_rim));
+      let blue_share = |color: Color| {
+         let hex = color.to_hex();
+         (hex & 0xff) as f32 - ((hex >> 16) & 0xff) as f32
+      };
+
+      let center = sphere_fragment(Vec3::new(0.0, 0.0, 1.0), 0.6);
+      assert_eq!(fragment_shader(&center, &ctx_rim, &ShaderType::Moon), fragment_shader(&center, &ctx_plain, &ShaderType::Moon));
+      for point in sphere_points(3000).into_iter().filter(|p| p.z > 0.0) {
+         let fragment = sphere_fragment(point, 0.6);
+         let (rim, plain) = (
+            fragment_shader(&fragment, &ctx_rim, &ShaderType::Moon),
+            fragment_shader(&fragment, &ctx_plain, &ShaderType::Moon),
+         );
+         if point.z > 0.98 {
+            // El fresnel es casi nulo de frente; a lo sumo cambia el redondeo
+            assert!((rim.luminance() - plain.luminance()).abs() <= 1.0 / 255.0, "center changed at {point:?}");
+         } else if point.z < 0.2 {
+            assert!(blue_share(rim) > blue_share(plain) + 20.0, "limb not tinted at {point:?}");
+         }
+      }
+   }
+
+   #[test]
+   fn polar_color_tints_caps_and_toggle_sets_the_edge() {
+      let caps = |polar_color: u32, soft: bool| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.polar_color = Color::from_hex(polar_color);
+         uniforms.params.rocky.soft_polar_edge = soft;
+         uniforms
+      };
+      // Sin especular, para que el brillo del hielo no tape el color del casquete
+      let light = LightingResult { diffuse: 1.0, specular: 0.0, ambient: 1.0 };
+      let shade = |uniforms: &Uniforms, point: Vec3| {
+         fragment_shader_lit(&sphere_fragment(point, 1.0), &FrameContext::new(uniforms), &ShaderType::RockyPlanet, light).to_hex()
+      };
+
+      let red_caps = caps(0xff0000, true);
+      for point in sphere_points(2000).into_iter().filter(|p| p.y.abs() > 0.95) {
+         let hex = shade(&red_caps, point);
+         assert!((hex >> 16) & 0xff > 200 && (hex >> 8) & 0xff < 40 && hex & 0xff < 40, "{hex:06x} at {point:?}");
+      }
+
+      // Con casquetes azules, el borde duro pasa de tierra a azul puro sin tonos intermedios
+      let meridian: Vec<Vec3> = (0..400)
+         .map(|i| {
+            let latitude = 0.75 + 0.24 * i as f32 / 400.0;
+            Vec3::new((1.0 - latitude * latitude).sqrt(), latitude, 0.0)
+         })
+         .collect();
+      let intermediate = |uniforms: &Uniforms| {
+         meridian.iter().filter(|point| (100..250).contains(&(shade(uniforms, **point) & 0xff))).count()
+      };
+      assert_eq!(intermediate(&caps(0x0000ff, false)), 0);
+      assert!(intermediate(&caps(0x0000ff, true)) > 5);
+      assert!(meridian.iter().any(|point| shade(&caps(0x0000ff, false), *point) == 0x0000ff));
+   }
+
+   #[test]
+   fn albedo_floor_keeps_dark_craters_off_black() {
+      // Terminador: sin difusa ni ambiente, solo queda el piso del material
+      let dark = LightingResult { diffuse: 0.0, specular: 0.0, ambient: 0.0 };
+      let shade = |uniforms: &Uniforms, point: Vec3| {
+         let ctx = FrameContext::new(uniforms);
+         fragment_shader_lit(&sphere_fragment(point, 0.0), &ctx, &ShaderType::Moon, dark)
+      };
+
+      let mut uniforms = single_layer_moon(0);
+      uniforms.params.moon.crater_blend = 1.0;
+      let ctx = FrameContext::new(&uniforms);
+      let (zoom, offset, _) = crater_layers(&uniforms.params.moon)[0];
+      let crater = deepest_crater(&ctx, zoom, offset);
+
+      assert_eq!(shade(&uniforms, crater).to_hex(), 0x000000);
+
+      uniforms.params.moon.albedo_floor = 0.3;
+      let floored = shade(&uniforms, crater);
+      assert!(floored.luminance() > 0.02, "crater collapsed to black: {:06x}", floored.to_hex());
+
+      // Y sigue viéndose como cráter: más oscuro que el mismo punto sin cráteres
+      let mut bare = uniforms.clone();
+      bare.params.moon.large_crater_density = 0.0;
+      assert!(floored.luminance() < shade(&bare, crater).luminance());
+   }
+
+   #[test]
+   fn great_spot_cycles_color_only_when_enabled() {
+      // Centro de la gran mancha; sin flujo, así lo único que depende del tiempo es el ciclo
+      let spot = Vec3::new(0.3, 0.2, (1.0f32 - 0.3 * 0.3 - 0.2 * 0.2).sqrt());
+      let shade = |speed: f32, time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.params.gas_giant.flow_direction = 0.0;
+         uniforms.params.gas_giant.storm_color_speed = speed;
+         uniforms.params.gas_giant.great_spot_blend = 1.0;
+         let ctx = FrameContext::new(&uniforms);
+         fragment_shader(&sphere_fragment(spot, 1.0), &ctx, &ShaderType::GasGiant).to_hex()
+      };
+
+      assert_ne!(shade(0.25, 0.0), shade(0.25, 2.0));
+      assert_eq!(shade(0.0, 0.0), shade(0.0, 2.0));
+   }
+
+   #[test]
+   fn band_sharpness_pushes_bands_to_the_edges() {
+      let ramp: Vec<f32> = (0..=20).map(|i| i as f32 / 20.0).collect();
+
+      // Sin nitidez la rampa del seno queda igual
+      for value in &ramp {
+         assert!((sharpen_band(*value, 0.0) - value).abs() < 1e-6);
+      }
+
+      // Con nitidez alta casi todo queda cerca de 0 o de 1, y el orden se mantiene
+      let sharp: Vec<f32> = ramp.iter().map(|value| sharpen_band(*value, 20.0)).collect();
+      let near_edges = sharp.iter().filter(|value| **value < 0.1 || **value > 0.9).count();
+      let soft_near_edges = ramp.iter().filter(|value| **value < 0.1 || **value > 0.9).count();
+      assert!(near_edges > soft_near_edges * 3, "{sharp:?}");
+      assert!(sharp.windows(2).all(|pair| pair[0] <= pair[1]));
+   }
+
+   #[test]
+   fn storm_coverage_adds_storms_and_altitude_scales_the_shadow_offset() {
+      // El noise de las tormentas varía poco sobre la esfera: se muestrean también varios tiempos
+      let stormy_samples = |coverage: f32| {
+         let layer = DustStormLayer { coverage, ..DustStormLayer::default() };
+         let mut count = 0;
+         for step in 0..40 {
+            let mut uniforms = test_uniforms();
+            uniforms.time = step as f32 * 250.0;
+            let ctx = FrameContext::new(&uniforms);
+            count += sphere_points(200).into_iter().filter(|p| dust_storm_factor(&ctx, &layer, 0, *p) > 0.0).count();
+         }
+         count
+      };
+      let counts = [0.4, 0.6, 0.8].map(stormy_samples);
+      assert!(counts[0] < counts[1] && counts[1] < counts[2], "{counts:?}");
+
+      // La sombra se lee desde un punto desplazado hacia la luz, tanto más cuanto más alta la capa
+      let mut uniforms = test_uniforms();
+      uniforms.light_direction = Vec3::new(1.0, 0.5, 0.2).normalize();
+      let ctx = FrameContext::new(&uniforms);
+      let point = Vec3::new(0.0, 0.0, 1.0);
+      let offset = |altitude: f32| dust_shadow_position(&ctx, &DustStormLayer { altitude, ..DustStormLayer::default() }, point) - point;
+      assert!((offset(0.2) - ctx.object_light_direction * 0.2).norm() < 1e-6);
+      assert!((offset(0.4) - offset(0.2) * 2.0).norm() < 1e-6);
+
+      // Y en el shader la sombra oscurece el suelo aunque la tormenta misma no se dibuje
+      let shadowed = |altitude: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.cloud_blend = 0.0;
+         uniforms.params.rocky.storm_layers = vec![DustStormLayer { coverage: 0.9, altitude, ..DustStormLayer::default() }];
+         uniforms
+      };
+      let (high, ground) = (shadowed(0.3), shadowed(0.0));
+      let (ctx_high, ctx_ground) = (FrameContext::new(&high), FrameContext::new(&ground));
+      let darker = sphere_points(500)
+         .into_iter()
+         .map(|p| sphere_fragment(p, 1.0))
+         .filter(|fragment| {
+            fragment_shader(fragment, &ctx_high, &ShaderType::RockyPlanet).luminance()
+               < fragment_shader(fragment, &ctx_ground, &ShaderType::RockyPlanet).luminance()
+         })
+         .count();
+      assert!(darker > 50, "the storm shadow never shows: {darker}");
+   }
+
+   #[test]
+   fn dense_night_side_cities_show_a_periodic_street_grid() {
+      let mut uniforms = test_uniforms();
+      uniforms.params.rocky.city_light_brightness = 1.0;
+      let ctx = FrameContext::new(&uniforms);
+      let mut dark_uniforms = test_uniforms();
+      dark_uniforms.params.rocky.city_light_brightness = 0.0;
+      let dark_ctx = FrameContext::new(&dark_uniforms);
+
+      // Lado nocturno: lo que suman las luces sobre el mismo fragmento sin ellas
+      let night = LightingResult { diffuse: 0.0, specular: 0.0, ambient: 1.0 };
+      let glow = |point: Vec3| {
+         let fragment = sphere_fragment(point, 0.0);
+         fragment_shader_lit(&fragment, &ctx, &ShaderType::RockyPlanet, night).luminance()
+            - fragment_shader_lit(&fragment, &dark_ctx, &ShaderType::RockyPlanet, night).luminance()
+      };
+      // Tramo corto a lo largo de x alrededor de `center`
+      let path = |center: Vec3| -> Vec<f32> {
+         (0..400).map(|i| glow((center + Vec3::new(0.3 * (i as f32 / 400.0 - 0.5), 0.0, 0.0)).normalize())).collect()
+      };
+
+      let temperate: Vec<Vec3> = sphere_points(4000).into_iter().filter(|p| p.y.abs() < 0.5).collect();
+      let by_density = |a: &Vec3, b: &Vec3| city_density(&ctx, *a).total_cmp(&city_density(&ctx, *b));
+      let dense = *temperate.iter().max_by(|a, b| by_density(a, b)).unwrap();
+      let sparse = *temperate.iter().min_by(|a, b| by_density(a, b)).unwrap();
+      assert!(city_density(&ctx, dense) > 0.9);
+
+      // Zona densa: varias calles separadas (tramos encendidos con huecos oscuros entre ellos)
+      let dense_path = path(dense);
+      let streets = dense_path.windows(2).filter(|pair| pair[0] <= 0.02 && pair[1] > 0.02).count();
+      assert!(streets >= 3, "only {streets} streets: {dense_path:?}");
+      assert!(dense_path.iter().any(|value| *value <= 0.02));
+
+      // Zona vacía: ninguna luz
+      assert!(path(sparse).iter().all(|value| *value <= 0.0), "lights in an empty region");
+   }
+
+   #[test]
+   fn default_lighting_reproduces_fragment_shader() {
+      let mut uniforms = test_uniforms();
+      uniforms.params.rocky.sea_level = Some(0.0);
+      uniforms.params.rocky.city_light_brightness = 1.0;
+      uniforms.params.moon.rim_strength = 0.5;
+      let ctx = FrameContext::new(&uniforms);
+      let shaders = [ShaderType::Sun, ShaderType::RockyPlanet, ShaderType::GasGiant, ShaderType::Moon, ShaderType::RingedPlanet];
+
+      for shader in &shaders {
+         for (index, point) in sphere_points(300).into_iter().enumerate() {
+            let fragment = sphere_fragment(point, index as f32 / 300.0);
+            assert_eq!(
+               fragment_shader_lit(&fragment, &ctx, shader, LightingResult::from_intensity(fragment.intensity)),
+               fragment_shader(&fragment, &ctx, shader),
+               "{shader:?}",
+            );
+         }
+      }
+   }
+
+   #[test]
+   fn detail_level_zero_drops_the_fine_layers_and_is_cheaper() {
+      let with_detail = |detail_level: u8, keep_fine_layers: bool| {
+         let mut uniforms = test_uniforms();
+         uniforms.detail_level = detail_level;
+         if !keep_fine_layers {
+            uniforms.params.rocky.detail_blend = 0.0;
+            uniforms.params.gas_giant.detail_blend = 0.0;
+            uniforms.params.moon.detail_blend = 0.0;
+            uniforms.params.moon.small_crater_density = 0.0;
+         }
+         uniforms
+      };
+      let (low, full, full_without_fine) = (with_detail(0, true), with_detail(2, true), with_detail(2, false));
+      let (ctx_low, ctx_full, ctx_without_fine) = (FrameContext::new(&low), FrameContext::new(&full), FrameContext::new(&full_without_fine));
+
+      // Calidad 0 es lo mismo que apagar a mano las capas finas, y sí cambia la imagen
+      for shader in [ShaderType::RockyPlanet, ShaderType::GasGiant, ShaderType::Moon] {
+         let mut changed = 0;
+         for point in sphere_points(500) {
+            let fragment = sphere_fragment(point, 0.8);
+            let cheap = fragment_shader(&fragment, &ctx_low, &shader);
+            assert_eq!(cheap, fragment_shader(&fragment, &ctx_without_fine, &shader), "{shader:?}");
+            if cheap != fragment_shader(&fragment, &ctx_full, &shader) {
+               changed += 1;
+            }
+         }
+         assert!(changed > 0, "{shader:?}: detail level has no visible effect");
+      }
+
+      // Y es más barato: con calidad 0 ningún fragmento llega a muestrear una capa fina
+      let fine_layer_samples = |ctx: &FrameContext| {
+         DETAIL_LAYER_SAMPLES.with(|samples| samples.set(0));
+         for shader in [ShaderType::Sun, ShaderType::RockyPlanet, ShaderType::GasGiant, ShaderType::Moon] {
+            for point in sphere_points(500) {
+               fragment_shader(&sphere_fragment(point, 0.8), ctx, &shader);
+            }
+         }
+         DETAIL_LAYER_SAMPLES.with(|samples| samples.get())
+      };
+      assert_eq!(fine_layer_samples(&ctx_low), 0);
+      assert!(fine_layer_samples(&ctx_full) > 0);
+   }
+
+   #[test]
+   fn ring_spirals_move_with_time_only_when_enabled() {
+      let position = Vec3::new(1.5, 0.0, 1.0);
+      let distance = (position.x * position.x + position.z * position.z).sqrt();
+      let factor_at = |strength: f32, time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.params.rings.spiral_strength = strength;
+         ring_spiral_factor(&FrameContext::new(&uniforms), position, distance)
+      };
+
+      let enabled: Vec<f32> = (0..8).map(|step| factor_at(0.3, step as f32 * 1.3)).collect();
+      let spread = enabled.iter().cloned().fold(f32::MIN, f32::max) - enabled.iter().cloned().fold(f32::MAX, f32::min);
+      assert!(spread > 0.1, "{enabled:?}");
+      assert!((0..8).all(|step| factor_at(0.0, step as f32 * 1.3) == 1.0));
+   }
+
+   #[test]
+   fn sunspots_are_darkest_at_the_center_and_lighten_toward_the_edge() {
+      let sun = |spot_darkness: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.sun.spot_darkness = spot_darkness;
+         uniforms.params.sun.edge_glow_blend = 0.0;
+         uniforms.params.sun.granulation_strength = 0.0;
+         uniforms
+      };
+      let (spotted, clean) = (sun(0.4), sun(0.0));
+      let (ctx_spotted, ctx_clean) = (FrameContext::new(&spotted), FrameContext::new(&clean));
+      let luminance = |rgb: [f32; 3]| 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+
+      // Oscurecimiento medio por tramo de noise dentro de las manchas (0.5..1 en 5 tramos)
+      let mut sums = [0.0f32; 5];
+      let mut counts = [0usize; 5];
+      for point in sphere_points(20_000) {
+         let noise = sunspot_noise(&ctx_spotted, point);
+         if noise <= 0.5 {
+            continue;
+         }
+         let fragment = sphere_fragment(point, 1.0);
+         let darkening = luminance(fragment_shader_hdr(&fragment, &ctx_clean, &ShaderType::Sun))
+            - luminance(fragment_shader_hdr(&fragment, &ctx_spotted, &ShaderType::Sun));
+         let bin = (((noise - 0.5) / 0.5 * 5.0) as usize).min(4);
+         sums[bin] += darkening;
+         counts[bin] += 1;
+      }
+      let means: Vec<f32> = sums.iter().zip(counts).filter(|(_, count)| *count > 0).map(|(sum, count)| sum / count as f32).collect();
+
+      assert!(means.len() >= 3, "too few spots on the disk: {counts:?}");
+      assert!(means.windows(2).all(|pair| pair[0] < pair[1]), "{means:?}");
+   }
+
+   #[test]
+   fn grazing_ocean_reflects_more_environment_than_head_on() {
+      // Planeta todo océano que refleja un entorno verde puro
+      let ocean = |environment: bool| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.sea_level = Some(2.0);
+         if environment {
+            uniforms.environment = Some(Arc::new(|_| Color::from_hex(0x00ff00)));
+         }
+         uniforms
+      };
+      let (reflective, plain) = (ocean(true), ocean(false));
+      let (ctx_reflective, ctx_plain) = (FrameContext::new(&reflective), FrameContext::new(&plain));
+      let unlit = LightingResult { diffuse: 0.0, specular: 0.0, ambient: 1.0 };
+      let reflected_green = |point: Vec3| {
+         let fragment = sphere_fragment(point, 0.0);
+         let green = |ctx: &FrameContext| (fragment_shader_lit(&fragment, ctx, &ShaderType::RockyPlanet, unlit).to_hex() >> 8 & 0xff) as i32;
+         green(&ctx_reflective) - green(&ctx_plain)
+      };
+
+      // La cámara de test_uniforms mira desde +z
+      let head_on = reflected_green(Vec3::new(0.0, 0.0, 1.0));
+      let grazing = reflected_green(Vec3::new(1.0, 0.0, 0.08));
+      assert!(head_on >= 0);
+      assert!(grazing > head_on + 40, "grazing {grazing} vs head-on {head_on}");
+   }
+
+   #[test]
+   fn reversed_spot_velocity_moves_the_detail_the_other_way() {
+      let offset = |velocity: Vec3, time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.params.gas_giant.spot_detail_velocity = velocity;
+         spot_detail_offset(&FrameContext::new(&uniforms))
+      };
+      let velocity = GasGiantParams::default().spot_detail_velocity;
+      let forward = offset(velocity, 3.0) - offset(velocity, 1.0);
+      let backward = offset(-velocity, 3.0) - offset(-velocity, 1.0);
+
+      // Por defecto hacia -x, como el `- time * 0.2` original
+      assert!(forward.x < 0.0 && forward.y == 0.0 && forward.z == 0.0, "{forward:?}");
+      assert!((forward + backward).norm() < 1e-6);
+      assert!(forward.norm() > 0.1);
+   }
+
+   #[test]
+   fn half_ambient_occlusion_halves_the_albedo() {
+      let uniforms = test_uniforms();
+      let ctx = FrameContext::new(&uniforms);
+      let halved = |full: u32, half: u32| {
+         [16, 8, 0].iter().all(|shift| {
+            let (full, half) = ((full >> shift & 0xff) as f32, (half >> shift & 0xff) as f32);
+            (half - full * 0.5).abs() <= 1.0
+         })
+      };
+
+      // Antes de la luz
+      let albedo = Color::from_hex(0xc08040);
+      let point = Vec3::new(0.3, 0.2, 0.9);
+      let (open, occluded) = (sphere_fragment(point, 1.0), sphere_fragment(point, 1.0).with_ao(0.5));
+      assert!(halved(surface_albedo(albedo, &open, &ctx).to_hex(), surface_albedo(albedo, &occluded, &ctx).to_hex()));
+
+      // Y la luz, que es lineal en el albedo, lo conserva: luna sin brillos ni contorno
+      let light = LightingResult { diffuse: 0.8, specular: 0.0, ambient: 1.0 };
+      for point in sphere_points(200) {
+         let (open, occluded) = (sphere_fragment(point, 0.8), sphere_fragment(point, 0.8).with_ao(0.5));
+         let shade = |fragment: &Fragment| fragment_shader_lit(fragment, &ctx, &ShaderType::Moon, light).to_hex();
+         assert!(halved(shade(&open), shade(&occluded)), "{point:?}");
+      }
+   }
+
+   #[test]
+   fn flipping_the_flow_direction_reverses_the_drift() {
+      let giant = |flow_direction: f32, time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.params.gas_giant.flow_direction = flow_direction;
+         uniforms
+      };
+      let flow_time = |flow_direction: f32, time: f32| gas_giant_flow_time(&FrameContext::new(&giant(flow_direction, time)));
+      assert!(flow_time(1.0, 2.0) > 0.0);
+      assert_eq!(flow_time(-1.0, 2.0), -flow_time(1.0, 2.0));
+
+      // Todas las derivas (turbulencia, tormentas, mancha) dependen del tiempo con signo:
+      // retrógrado en t se ve igual que prógrado en -t, y distinto de prógrado en t
+      let (retrograde, mirrored, prograde) = (giant(-1.0, 20.0), giant(1.0, -20.0), giant(1.0, 20.0));
+      let (ctx_retrograde, ctx_mirrored, ctx_prograde) =
+         (FrameContext::new(&retrograde), FrameContext::new(&mirrored), FrameContext::new(&prograde));
+      let mut differs = 0;
+      for point in sphere_points(500) {
+         let fragment = sphere_fragment(point, 1.0);
+         let shaded = fragment_shader(&fragment, &ctx_retrograde, &ShaderType::GasGiant);
+         assert_eq!(shaded, fragment_shader(&fragment, &ctx_mirrored, &ShaderType::GasGiant));
+         if shaded != fragment_shader(&fragment, &ctx_prograde, &ShaderType::GasGiant) {
+            differs += 1;
+         }
+      }
+      assert!(differs > 20, "flow direction barely matters: {differs}");
+   }
+
+   #[test]
+   fn storm_layers_compose_and_zero_layers_leave_the_surface_clear() {
+      let slow = DustStormLayer { coverage: 0.9, speed: 0.1, ..DustStormLayer::default() };
+      let fast = DustStormLayer { coverage: 0.9, speed: 0.5, zoom: 12.0, ..DustStormLayer::default() };
+      let rocky = |layers: Vec<DustStormLayer>, time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.params.rocky.storm_layers = layers;
+         uniforms
+      };
+      // Lejos de los casquetes, que tapan las tormentas
+      let points: Vec<Vec3> = sphere_points(200).into_iter().filter(|p| p.y.abs() < 0.7).collect();
+      // Cuánto cambia cada fragmento entre pasos de tiempo seguidos con estas capas.
+      // Las tormentas van y vienen despacio, así que se mira un tramo largo
+      let changes = |layers: &[DustStormLayer]| -> Vec<f32> {
+         let shade = |time: f32| -> Vec<f32> {
+            let uniforms = rocky(layers.to_vec(), time);
+            let ctx = FrameContext::new(&uniforms);
+            points.iter().map(|p| fragment_shader(&sphere_fragment(*p, 1.0), &ctx, &ShaderType::RockyPlanet).luminance()).collect()
+         };
+         let frames: Vec<Vec<f32>> = (0..40).map(|step| shade(step as f32 * 50.0)).collect();
+         frames.windows(2).flat_map(|pair| pair[1].iter().zip(&pair[0]).map(|(after, before)| after - before).collect::<Vec<f32>>()).collect()
+      };
+
+      let double = changes(&[slow.clone(), fast]);
+      let single = changes(&[slow]);
+      assert!(single.iter().any(|change| *change != 0.0));
+      let different = single.iter().zip(&double).filter(|(a, b)| (*a - *b).abs() > 0.01).count();
+      assert!(different > 100, "the second layer does not change the animation: {different}");
+
+      // Sin capas no hay tormentas: nada se anima y la superficie es la de sin nubes
+      assert!(changes(&[]).iter().all(|change| *change == 0.0));
+      let mut hidden = rocky(vec![DustStormLayer::default()], 10.0);
+      hidden.params.rocky.cloud_blend = 0.0;
+      let (clear, ctx_hidden) = (rocky(Vec::new(), 10.0), FrameContext::new(&hidden));
+      let ctx_clear = FrameContext::new(&clear);
+      for point in &points {
+         let fragment = sphere_fragment(*point, 1.0);
+         assert_eq!(
+            fragment_shader(&fragment, &ctx_clear, &ShaderType::RockyPlanet),
+            fragment_shader(&fragment, &ctx_hidden, &ShaderType::RockyPlanet),
+         );
+      }
+   }
+
+   #[test]
+   fn sphere_uv_fallback_textures_meshes_without_uvs() {
+      // Textura de 8x4 texels con un color distinto por texel
+      let texture = |fragment: &Fragment, ctx: &FrameContext, _: &LightingResult| {
+         let uv = fragment_uv(fragment, ctx);
+         let texel = ((uv.x * 8.0) as u32).min(7) + ((uv.y * 4.0) as u32).min(3) * 8;
+         Color::new((texel * 7) as u8, 0, 0)
+      };
+      let distinct_texels = |fallback: bool| {
+         let mut uniforms = test_uniforms();
+         uniforms.sphere_uv_fallback = fallback;
+         uniforms.custom_shaders = vec![Arc::new(texture)];
+         let ctx = FrameContext::new(&uniforms);
+         // sphere_fragment no da tex_coords: quedan en cero, como en una malla sin UVs
+         let mut colors: Vec<u32> = sphere_points(500)
+            .into_iter()
+            .map(|p| fragment_shader(&sphere_fragment(p, 1.0), &ctx, &ShaderType::Custom(0)).to_hex())
+            .collect();
+         colors.sort();
+         colors.dedup();
+         colors.len()
+      };
+
+      assert_eq!(distinct_texels(false), 1);
+      assert!(distinct_texels(true) >= 24, "{}", distinct_texels(true));
+   }
+
+   #[test]
+   fn small_photosphere_leaves_corona_outside_the_disk() {
+      let sun = |photosphere_radius: Option<f32>| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.sun.photosphere_radius = photosphere_radius;
+         uniforms
+      };
+      let (small, full) = (sun(Some(0.5)), sun(None));
+      let (ctx_small, ctx_full) = (FrameContext::new(&small), FrameContext::new(&full));
+      let luminance = |rgb: [f32; 3]| 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+      let brightness = |ctx: &FrameContext, point: Vec3| luminance(fragment_shader_hdr(&sphere_fragment(point, 1.0), ctx, &ShaderType::Sun));
+
+      // La cámara mira desde +z: el radio aparente de un punto es su distancia al eje z
+      let at_radius = |radius: f32| Vec3::new(radius, 0.0, (1.0 - radius * radius).sqrt());
+      let mut previous = brightness(&ctx_small, Vec3::new(0.0, 0.0, 1.0));
+      for apparent_radius in [0.6f32, 0.75, 0.9] {
+         let point = at_radius(apparent_radius);
+         // Solo corona: el naranja liso, sin plasma ni manchas del disco, y más tenue hacia afuera
+         let [r, g, b] = fragment_shader_hdr(&sphere_fragment(point, 1.0), &ctx_small, &ShaderType::Sun);
+         assert!((g / r - 140.0 / 255.0).abs() < 1e-3 && b == 0.0, "radius {apparent_radius}: {r} {g} {b}");
+         let corona = brightness(&ctx_small, point);
+         assert!(corona < previous && corona < brightness(&ctx_full, point), "radius {apparent_radius}: {corona}");
+         previous = corona;
+      }
+      assert!(previous < brightness(&ctx_full, at_radius(0.9)) * 0.2);
+      // Dentro de la fotosfera el disco queda igual
+      let inside = at_radius(0.3);
+      assert_eq!(brightness(&ctx_small, inside), brightness(&ctx_full, inside));
+   }
+
+   #[test]
+   fn shader_mask_selects_and_blends_the_two_shaders() {
+      let uniforms = test_uniforms();
+      let ctx = FrameContext::new(&uniforms);
+      let (a, b) = (ShaderType::Moon, ShaderType::RockyPlanet);
+      let channels = |color: Color| {
+         let hex = color.to_hex();
+         [hex >> 16 & 0xff, hex >> 8 & 0xff, hex & 0xff].map(|channel| channel as i32)
+      };
+
+      for point in sphere_points(200) {
+         let fragment = sphere_fragment(point, 0.7);
+         let (color_a, color_b) = (fragment_shader(&fragment, &ctx, &a), fragment_shader(&fragment, &ctx, &b));
+         assert_eq!(fragment_shader_masked(&fragment, &ctx, &a, &b, 0.0), color_a);
+         assert_eq!(fragment_shader_masked(&fragment, &ctx, &a, &b, 1.0), color_b);
+
+         // Un cuarto del camino de `a` a `b`, canal por canal
+         let blended = channels(fragment_shader_masked(&fragment, &ctx, &a, &b, 0.25));
+         let (from, to) = (channels(color_a), channels(color_b));
+         for channel in 0..3 {
+            let expected = from[channel] as f32 + (to[channel] - from[channel]) as f32 * 0.25;
+            assert!((blended[channel] as f32 - expected).abs() <= 1.0, "{point:?}");
+         }
+      }
+   }
+
+   #[test]
+   fn body_radius_keeps_feature_density_on_larger_meshes() {
+      let with_radius = |body_radius: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.body_radius = body_radius;
+         uniforms
+      };
+      let (unit, compensated) = (with_radius(1.0), with_radius(2.0));
+      let (ctx_unit, ctx_compensated) = (FrameContext::new(&unit), FrameContext::new(&compensated));
+
+      // Meridiano de la esfera de radio `mesh_radius`, con la normal de la esfera
+      let meridian = |mesh_radius: f32| -> Vec<Fragment> {
+         (0..400)
+            .map(|i| {
+               let angle = i as f32 / 400.0 * std::f32::consts::PI;
+               let direction = Vec3::new(angle.sin(), angle.cos(), 0.3).normalize();
+               sphere_fragment(direction, 1.0).with_vertex_position(direction * mesh_radius)
+            })
+            .collect()
+      };
+      // Bordes de bandas: cambios bruscos de color a lo largo del meridiano
+      let band_edges = |ctx: &FrameContext, fragments: &[Fragment]| {
+         let shaded: Vec<f32> = fragments.iter().map(|f| fragment_shader(f, ctx, &ShaderType::GasGiant).luminance()).collect();
+         shaded.windows(3).filter(|w| (w[1] - w[0]) * (w[2] - w[1]) < 0.0).count()
+      };
+
+      let (small, large) = (meridian(1.0), meridian(2.0));
+      for (fragment_small, fragment_large) in small.iter().zip(&large) {
+         for shader in [ShaderType::RockyPlanet, ShaderType::GasGiant, ShaderType::Moon] {
+            assert_eq!(fragment_shader(fragment_small, &ctx_unit, &shader), fragment_shader(fragment_large, &ctx_compensated, &shader));
+         }
+      }
+      // Sin compensar (radio 1), la malla grande tiene más bandas en el mismo meridiano
+      assert!(band_edges(&ctx_unit, &large) > band_edges(&ctx_unit, &small) * 3 / 2);
+   }
+
+   #[test]
+   fn polar_vortex_swirls_high_latitudes_only() {
+      let vortex = |time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         uniforms.params.gas_giant.vortex_strength = 0.3;
+         uniforms
+      };
+      let around_pole = |point: Vec3| point.z.atan2(point.x);
+
+      for time in [0.0, 5.0] {
+         let uniforms = vortex(time);
+         let ctx = FrameContext::new(&uniforms);
+         for longitude in [0.0f32, 1.0, 2.5, 4.0] {
+            // Cerca del polo el muestreo gira alrededor del eje y sin cambiar de latitud
+            let polar = Vec3::new(0.2 * longitude.cos(), 0.97, 0.2 * longitude.sin());
+            let (swirled, factor) = polar_vortex_swirl(&ctx, polar);
+            assert!(factor > 0.0);
+            assert!((swirled.y - polar.y).abs() < 1e-5);
+            assert!((around_pole(swirled) - around_pole(polar)).abs() > 0.05, "{polar:?} -> {swirled:?}");
+
+            // En el ecuador no se mueve
+            let equatorial = Vec3::new(longitude.cos(), 0.1, longitude.sin());
+            assert_eq!(polar_vortex_swirl(&ctx, equatorial), (equatorial, 0.0));
+         }
+      }
+
+      // Y el giro avanza con el tiempo
+      let polar = Vec3::new(0.2, 0.97, 0.0);
+      let swirl_at = |time: f32| polar_vortex_swirl(&FrameContext::new(&vortex(time)), polar).0;
+      assert!((around_pole(swirl_at(5.0)) - around_pole(swirl_at(0.0))).abs() > 0.05);
+   }
+
+   #[test]
+   fn albedo_scale_halves_the_base_color_and_one_is_identity() {
+      let scaled = |albedo_scale: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.albedo_scale = albedo_scale;
+         uniforms
+      };
+      let (half, one) = (scaled(0.5), scaled(1.0));
+      let (ctx_half, ctx_one) = (FrameContext::new(&half), FrameContext::new(&one));
+      let fragment = sphere_fragment(Vec3::new(0.2, 0.4, 0.9), 1.0);
+
+      for hex in [0xc08040, 0xffffff, 0x3a5f7d, 0x000000] {
+         let albedo = Color::from_hex(hex);
+         assert_eq!(surface_albedo(albedo, &fragment, &ctx_one), albedo);
+         let halved = surface_albedo(albedo, &fragment, &ctx_half).to_hex();
+         for shift in [16, 8, 0] {
+            let (full, half) = ((hex >> shift & 0xff) as f32, (halved >> shift & 0xff) as f32);
+            assert!((half - full * 0.5).abs() <= 1.0, "{hex:06x} -> {halved:06x}");
+         }
+      }
+
+      // En el shader completo la escala 1 no cambia nada respecto de los valores por defecto
+      let defaults = test_uniforms();
+      let ctx_defaults = FrameContext::new(&defaults);
+      for shader in [ShaderType::RockyPlanet, ShaderType::GasGiant, ShaderType::Moon] {
+         assert_eq!(fragment_shader(&fragment, &ctx_one, &shader), fragment_shader(&fragment, &ctx_defaults, &shader));
+      }
+   }
+
+   #[test]
+   fn high_storms_render_lighter_than_deep_ones() {
+      let storm_at_depth = |depth: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.gas_giant.storms = vec![StormSpot {
+            latitude: 0.2,
+            longitude: 3.0,
+            radius: 0.2,
+            color: Color::from_hex(0xc74440),
+            depth,
+         }];
+         uniforms
+      };
+      let (high, deep) = (storm_at_depth(0.0), storm_at_depth(1.0));
+      let (ctx_high, ctx_deep) = (FrameContext::new(&high), FrameContext::new(&deep));
+      let center = high.params.gas_giant.storms[0].center();
+
+      // Mismo lugar y mismo noise: solo cambia la profundidad
+      for offset in [Vec3::zeros(), Vec3::new(0.05, 0.0, 0.0), Vec3::new(0.0, 0.05, 0.0)] {
+         let fragment = sphere_fragment(center + offset, 1.0);
+         let (lighter, darker) = (
+            fragment_shader(&fragment, &ctx_high, &ShaderType::GasGiant).luminance(),
+            fragment_shader(&fragment, &ctx_deep, &ShaderType::GasGiant).luminance(),
+         );
+         assert!(lighter > darker + 0.05, "{offset:?}: {lighter} vs {darker}");
+      }
+   }
+
+   #[test]
+   fn rough_moon_stays_brighter_near_the_terminator() {
+      // Luz detrás de la cámara (luna llena): el terminador cae sobre el limbo
+      let moon = |roughness: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.moon.oren_nayar_roughness = roughness;
+         uniforms
+      };
+      let (smooth, rough) = (moon(0.0), moon(0.8));
+      let (ctx_smooth, ctx_rough) = (FrameContext::new(&smooth), FrameContext::new(&rough));
+      let light = smooth.light_direction;
+
+      let angle = 80f32.to_radians();
+      let near_terminator = Vec3::new(angle.sin(), 0.0, angle.cos());
+      let lambert = near_terminator.dot(&light);
+      assert!(lambert > 0.0 && lambert < 0.2);
+      assert!(oren_nayar_factor(&near_terminator, &light, &ctx_rough.view_direction, 0.8) > 1.2);
+      assert_eq!(oren_nayar_factor(&near_terminator, &light, &ctx_smooth.view_direction, 0.0), 1.0);
+
+      // Sin ambiente para que solo cuente la difusa
+      let diffuse_only = LightingResult { diffuse: lambert, specular: 0.0, ambient: 0.0 };
+      let fragment = sphere_fragment(near_terminator, lambert);
+      let shade = |ctx: &FrameContext| fragment_shader_lit(&fragment, ctx, &ShaderType::Moon, diffuse_only);
+      assert!(shade(&ctx_rough).luminance() > shade(&ctx_smooth).luminance());
+
+      // Con rugosidad 0 es el Lambert de siempre: el brillo sigue linealmente a N·L
+      let doubled = LightingResult { diffuse: lambert * 2.0, ..diffuse_only };
+      let brighter = fragment_shader_lit(&fragment, &ctx_smooth, &ShaderType::Moon, doubled);
+      assert!((brighter.luminance() / shade(&ctx_smooth).luminance() - 2.0).abs() < 0.1);
+   }
+
+   #[test]
+   fn frame_rotations_match_the_per_fragment_math() {
+      let mut uniforms = test_uniforms();
+      uniforms.time = 3.7;
+      uniforms.model_matrix = nalgebra_glm::rotate(&Mat4::identity(), 0.9, &Vec3::new(0.2, 1.0, 0.1));
+      uniforms.light_direction = Vec3::new(1.0, 0.3, 0.5).normalize();
+      uniforms.params.gas_giant.vortex_strength = 0.3;
+      let ctx = FrameContext::new(&uniforms);
+
+      // Lo que antes se hacía en cada fragmento: trigonometría con el tiempo del frame
+      let gas_giant = &uniforms.params.gas_giant;
+      let turns = gas_giant.vortex_strength + uniforms.surface_time() * gas_giant.vortex_speed;
+      let (sin, cos) = (turns * 2.0 * std::f32::consts::PI).sin_cos();
+      for point in sphere_points(300).into_iter().filter(|p| p.y > 0.85) {
+         let naive_rotated = Vec3::new(point.x * cos - point.z * sin, point.y, point.x * sin + point.z * cos);
+         let factor = smoothstep(gas_giant.vortex_latitude, 1.0, point.y);
+         let naive = point + (naive_rotated - point) * factor;
+         let (swirled, _) = polar_vortex_swirl(&ctx, point);
+         assert!((swirled - naive).norm() < 1e-5, "{point:?}");
+      }
+      let naive_light = nalgebra_glm::mat4_to_mat3(&uniforms.model_matrix).try_inverse().unwrap() * uniforms.light_direction;
+      assert!((ctx.object_light_direction - naive_light.normalize()).norm() < 1e-5);
+
+      // El camino por fragmento solo usa la matriz ya calculada: si se cambia, el resultado la sigue
+      let mut frozen = FrameContext::new(&uniforms);
+      frozen.rotations.polar_vortex = nalgebra_glm::Mat3::identity();
+      let pole_side = Vec3::new(0.2, 0.97, 0.1);
+      assert_eq!(polar_vortex_swirl(&frozen, pole_side).0, pole_side);
+
+      // Los centros de las tormentas y el color de la gran mancha también salen del frame
+      let mut stormy = uniforms.clone();
+      stormy.params.gas_giant.storms = crate::params::generate_storms(7, 4);
+      stormy.params.gas_giant.storm_color_speed = 0.25;
+      let ctx = FrameContext::new(&stormy);
+      let gas_giant = &stormy.params.gas_giant;
+      for (storm, center) in gas_giant.storms.iter().zip(&ctx.storm_centers) {
+         assert_eq!(*center, storm.center());
+      }
+      assert_eq!(ctx.storm_centers.len(), gas_giant.storms.len());
+      let cycle = 0.5 - 0.5 * (stormy.effective_time() * 0.25 * 2.0 * std::f32::consts::PI).cos();
+      assert_eq!(ctx.great_spot_color, gas_giant.storm_color_gradient.sample(cycle));
+   }
+
+   #[test]
+   fn spicules_break_up_the_limb_and_leave_the_interior_smooth() {
+      let sun = |spicule_intensity: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.sun.spicule_intensity = spicule_intensity;
+         uniforms
+      };
+      let (spiky, smooth) = (sun(1.0), sun(0.0));
+      let (ctx_spiky, ctx_smooth) = (FrameContext::new(&spiky), FrameContext::new(&smooth));
+      let luminance = |rgb: [f32; 3]| 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+      // Lo que suman las espículas alrededor de un anillo del disco (la cámara mira desde +z)
+      let ring = |radius: f32| -> Vec<f32> {
+         (0..360)
+            .map(|degree| {
+               let angle = (degree as f32).to_radians();
+               let point = Vec3::new(radius * angle.cos(), radius * angle.sin(), (1.0 - radius * radius).sqrt());
+               let fragment = sphere_fragment(point, 1.0);
+               luminance(fragment_shader_hdr(&fragment, &ctx_spiky, &ShaderType::Sun))
+                  - luminance(fragment_shader_hdr(&fragment, &ctx_smooth, &ShaderType::Sun))
+            })
+            .collect()
+      };
+      let peaks = |values: &[f32]| values.windows(3).filter(|w| w[1] > w[0] && w[1] > w[2]).count();
+
+      // Borde: muchos filamentos a lo largo de la vuelta
+      let limb = ring(0.98);
+      assert!(limb.iter().any(|value| *value > 0.05));
+      assert!(peaks(&limb) >= 20, "only {} filaments", peaks(&limb));
+      // Interior: nada
+      assert!(ring(0.3).iter().all(|value| *value == 0.0));
+      // Apagadas: el limbo es el de siempre
+      let defaults = test_uniforms();
+      let ctx_defaults = FrameContext::new(&defaults);
+      for point in sphere_points(200) {
+         let fragment = sphere_fragment(point, 1.0);
+         assert_eq!(fragment_shader_hdr(&fragment, &ctx_smooth, &ShaderType::Sun), fragment_shader_hdr(&fragment, &ctx_defaults, &ShaderType::Sun));
+      }
+   }
+
+   #[test]
+   fn tangent_follows_the_normal_through_the_model_matrix() {
+      let rotation = nalgebra_glm::rotate(&Mat4::identity(), 1.1, &Vec3::new(0.3, 1.0, -0.4));
+      let stretched = nalgebra_glm::scale(&rotation, &Vec3::new(2.0, 0.5, 1.0));
+      let rotation3 = nalgebra_glm::mat4_to_mat3(&rotation);
+
+      for vertex in crate::sphere::uv_sphere(6, 12) {
+         // Los polos de la esfera UV tienen una tangente de respaldo; igual debe ser perpendicular
+         assert!(vertex.normal.dot(&vertex.tangent).abs() < 1e-4);
+
+         let mut uniforms = test_uniforms();
+         uniforms.model_matrix = rotation;
+         let rotated = vertex_shader(&vertex, &uniforms);
+         assert!((rotated.transformed_normal - rotation3 * vertex.normal).norm() < 1e-4);
+         assert!((rotated.transformed_tangent - rotation3 * vertex.tangent).norm() < 1e-4);
+         assert!(rotated.transformed_normal.dot(&rotated.transformed_tangent).abs() < 1e-4);
+         assert!((rotated.bitangent().norm() - 1.0).abs() < 1e-3);
+
+         // Con escala no uniforme la base sigue siendo perpendicular
+         uniforms.model_matrix = stretched;
+         let scaled = vertex_shader(&vertex, &uniforms);
+         let cosine = scaled.transformed_normal.normalize().dot(&scaled.transformed_tangent.normalize());
+         assert!(cosine.abs() < 1e-4, "{cosine}");
+      }
+   }
+
+   #[test]
+   fn halving_emissive_scale_halves_the_lava_glow_only() {
+      let young = |emissive_scale: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.surface_age = 0.0;
+         uniforms.emissive_scale = emissive_scale;
+         uniforms
+      };
+      let (full, half, off) = (young(1.0), young(0.5), young(0.0));
+      let (ctx_full, ctx_half, ctx_off) = (FrameContext::new(&full), FrameContext::new(&half), FrameContext::new(&off));
+      // Lado nocturno, para que la corteza iluminada no sume demasiado a la lava
+      let night = LightingResult { diffuse: 0.0, specular: 0.0, ambient: 1.0 };
+      let channels = |ctx: &FrameContext, fragment: &Fragment| {
+         let hex = fragment_shader_lit(fragment, ctx, &ShaderType::RockyPlanet, night).to_hex();
+         [16, 8, 0].map(|shift| (hex >> shift & 0xff) as f32)
+      };
+
+      let (mut lava, mut crust) = (0, 0);
+      for point in sphere_points(3000) {
+         let fragment = sphere_fragment(point, 0.0);
+         let (glowing, dimmed, base) = (channels(&ctx_full, &fragment), channels(&ctx_half, &fragment), channels(&ctx_off, &fragment));
+         if glowing == base {
+            // Corteza sin lava: la escala no la toca
+            assert_eq!(dimmed, base);
+            crust += 1;
+         } else {
+            // Los canales que llegan a 255 quedan recortados y no se pueden comparar
+            for channel in (0..3).filter(|channel| glowing[*channel] < 255.0) {
+               let (full_glow, half_glow) = (glowing[channel] - base[channel], dimmed[channel] - base[channel]);
+               assert!((half_glow - full_glow * 0.5).abs() <= 1.0, "{point:?}: {full_glow} vs {half_glow}");
+            }
+            lava += 1;
+         }
+      }
+      assert!(lava > 50 && crust > 50, "lava {lava}, crust {crust}");
+   }
+
+   #[test]
+   fn horizontal_spot_drift_advances_in_x() {
+      let drift = |velocity: Option<Vec3>, time: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.time = time;
+         if let Some(velocity) = velocity {
+            uniforms.params.sun.spot_drift_velocity = velocity;
+         }
+         sunspot_drift(&FrameContext::new(&uniforms))
+      };
+
+      // Por defecto, como el `+ time * 0.1` original con zoom 3: solo en Y y a la misma
+      // velocidad sobre la esfera, aunque ahora el zoom de las manchas sea mayor
+      let vertical = (drift(None, 4.0) - drift(None, 2.0)) / SUNSPOT_ZOOM;
+      assert!((vertical - Vec3::new(0.0, 0.2 / 3.0, 0.0)).norm() < 1e-6, "{vertical:?}");
+
+      let horizontal = drift(Some(Vec3::new(0.1, 0.0, 0.0)), 4.0) - drift(Some(Vec3::new(0.1, 0.0, 0.0)), 2.0);
+      assert!(horizontal.x > 0.0 && horizontal.y == 0.0 && horizontal.z == 0.0, "{horizontal:?}");
+
+      // Y las manchas se mueven con ese desplazamiento: muestrear más tarde es muestrear corrido en X
+      let mut later = test_uniforms();
+      later.time = 4.0;
+      later.params.sun.spot_drift_velocity = Vec3::new(0.1, 0.0, 0.0);
+      let mut earlier = later.clone();
+      earlier.time = 2.0;
+      let (ctx_later, ctx_earlier) = (FrameContext::new(&later), FrameContext::new(&earlier));
+      let point = Vec3::new(0.3, 0.5, 0.8);
+      let shifted = point + horizontal / SUNSPOT_ZOOM;
+      assert!((sunspot_noise(&ctx_later, point) - sunspot_noise(&ctx_earlier, shifted)).abs() < 1e-4);
+   }
+
+   #[test]
+   fn old_surfaces_crater_and_young_ones_show_lava() {
+      let features = |surface_age: f32| {
+         let mut uniforms = test_uniforms();
+         uniforms.params.rocky.surface_age = surface_age;
+         let ctx = FrameContext::new(&uniforms);
+         let (mut craters, mut lava) = (0, 0);
+         for point in sphere_points(4000) {
+            let (crater_depth, lava_factor) = surface_age_features(&ctx, point);
+            craters += (crater_depth > 0.0) as usize;
+            lava += (lava_factor > 0.0) as usize;
+         }
+         (craters, lava)
+      };
+
+      let (old, moderate, young) = (features(1.0), features(0.5), features(0.0));
+      assert_eq!(moderate, (0, 0));
+      assert!(old.0 > 100 && old.1 == 0, "old: {old:?}");
+      assert!(young.1 > 100 && young.0 == 0, "young: {young:?}");
+      // Y en el medio, en proporción: más edad, más cráteres; menos edad, más lava
+      assert!(features(0.75).0 < old.0);
+      assert!(features(0.25).1 < young.1);
+
+      // El terreno joven es más liso que el viejo, y la edad 0.5 deja la rugosidad original
+      let roughness = |surface_age: f32| {
+         (-10..=10).map(|i| terrain_roughness(surface_age, i as f32 / 10.0)).sum::<f32>()
+      };
+      assert!(roughness(0.0) < roughness(0.5) && roughness(0.5) < roughness(1.0));
+      assert!((terrain_roughness(0.5, 0.6) - (0.6 * 0.7 + 0.3)).abs() < 1e-6);
+   }
+}
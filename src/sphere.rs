@@ -0,0 +1,129 @@
+use nalgebra_glm::{Vec2, Vec3};
+use std::f32::consts::PI;
+use crate::vertex::Vertex;
+use crate::shaders::Uniforms;
+
+// Malla de esfera unitaria para los helpers de render. Más subdivisiones dan
+// siluetas más redondas a cambio de más triángulos que rasterizar.
+pub struct SphereMesh {
+   pub vertices: Vec<Vertex>,
+}
+
+impl SphereMesh {
+   // `subdivisions` anillos de latitud y el doble en longitud (mínimo 2)
+   pub fn new(subdivisions: usize) -> Self {
+      let stacks = subdivisions.max(2);
+      SphereMesh {
+         vertices: uv_sphere(stacks, stacks * 2),
+      }
+   }
+
+   pub fn triangle_count(&self) -> usize {
+      self.vertices.len() / 3
+   }
+}
+
+// Coordenadas de textura de un punto de la esfera, con la misma convención que
+// uv_sphere (u da la vuelta en longitud, v va de polo norte a polo sur)
+pub fn sphere_uv(position: Vec3) -> Vec2 {
+   let direction = position.normalize();
+   let u = (direction.z.atan2(direction.x) / (2.0 * PI)).rem_euclid(1.0);
+   let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+   Vec2::new(u, v)
+}
+
+// Esfera UV de radio 1 como lista de triángulos (3 vértices por triángulo),
+// con el mismo formato que Obj::get_vertex_array
+pub fn uv_sphere(stacks: usize, slices: usize) -> Vec<Vertex> {
+   let point = |stack: usize, slice: usize| {
+      let theta = stack as f32 / stacks as f32 * PI;
+      let phi = slice as f32 / slices as f32 * 2.0 * PI;
+      let position = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+      let tex_coords = Vec2::new(slice as f32 / slices as f32, stack as f32 / stacks as f32);
+      Vertex::new(position, position, tex_coords)
+   };
+
+   let mut vertices = Vec::with_capacity(stacks * slices * 6);
+   for stack in 0..stacks {
+      for slice in 0..slices {
+         let top_left = point(stack, slice);
+         let top_right = point(stack, slice + 1);
+         let bottom_left = point(stack + 1, slice);
+         let bottom_right = point(stack + 1, slice + 1);
+
+         vertices.push(top_left.clone());
+         vertices.push(bottom_left.clone());
+         vertices.push(top_right.clone());
+
+         vertices.push(top_right);
+         vertices.push(bottom_left);
+         vertices.push(bottom_right);
+      }
+   }
+
+   vertices
+}
+
+// Puntos del terminador (línea día/noche) sobre la esfera unitaria, en espacio objeto.
+// Son los puntos donde dot(normal, luz) = 0: un círculo máximo perpendicular a la
+// dirección de la luz llevada al espacio del cuerpo.
+pub fn terminator_points(uniforms: &Uniforms, samples: usize) -> Vec<Vec3> {
+   let light = uniforms.object_light_direction();
+
+   let helper = if light.y.abs() < 0.99 {
+      Vec3::new(0.0, 1.0, 0.0)
+   } else {
+      Vec3::new(1.0, 0.0, 0.0)
+   };
+   let u = helper.cross(&light).normalize();
+   let v = light.cross(&u);
+
+   (0..samples)
+      .map(|i| {
+         let angle = i as f32 / samples as f32 * 2.0 * PI;
+         u * angle.cos() + v * angle.sin()
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use nalgebra_glm::Mat4;
+
+   fn lit_uniforms(model_matrix: Mat4, light_direction: Vec3) -> Uniforms {
+      let mut uniforms = Uniforms::new(model_matrix, Mat4::identity(), Mat4::identity(), Mat4::identity(), 0.0);
+      uniforms.light_direction = light_direction.normalize();
+      uniforms
+   }
+
+   #[test]
+   fn terminator_points_are_perpendicular_to_the_light() {
+      let lights = [
+         Vec3::new(0.0, 0.0, 1.0),
+         Vec3::new(1.0, 0.0, 0.0),
+         Vec3::new(0.0, 1.0, 0.0),
+         Vec3::new(1.0, -2.0, 0.5),
+      ];
+      let rotation = nalgebra_glm::rotate(&Mat4::identity(), 0.7, &Vec3::new(0.3, 1.0, 0.2));
+
+      for light in lights {
+         for model in [Mat4::identity(), rotation] {
+            let uniforms = lit_uniforms(model, light);
+            let object_light = uniforms.object_light_direction();
+            let points = terminator_points(&uniforms, 32);
+            assert_eq!(points.len(), 32);
+
+            for point in points {
+               // Sobre la esfera unitaria y en el plano perpendicular a la luz
+               assert!((point.magnitude() - 1.0).abs() < 1e-4, "{point:?} fuera de la esfera");
+               assert!(point.dot(&object_light).abs() < 1e-4, "{point:?} no es perpendicular a {light:?}");
+
+               // Llevado al mundo sigue siendo perpendicular a la luz original
+               let world = nalgebra_glm::mat4_to_mat3(&model) * point;
+               assert!(world.dot(&light.normalize()).abs() < 1e-4);
+            }
+         }
+      }
+   }
+}
@@ -1,16 +1,14 @@
 use nalgebra_glm::{Vec3, dot};
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
-use crate::color::Color;
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+// `light_dir` apunta hacia la luz, en el mismo espacio que las normales transformadas
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, light_dir: &Vec3) -> Vec<Fragment> {
    let mut fragments = Vec::new();
    let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
 
    let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
 
-   let light_dir = Vec3::new(0.0, 0.0, 1.0);
-
    let triangle_area = edge_function(&a, &b, &c);
 
    // Iterate over each pixel in the bounding box
@@ -22,34 +20,42 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
          let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
 
          // Check if the point is inside the triangle
-         if w1 >= 0.0 && w1 <= 1.0 && 
-            w2 >= 0.0 && w2 <= 1.0 &&
-            w3 >= 0.0 && w3 <= 1.0 {
+         if (0.0..=1.0).contains(&w1) &&
+            (0.0..=1.0).contains(&w2) &&
+            (0.0..=1.0).contains(&w3) {
                
                // Interpolate normal
                let normal = (v1.transformed_normal * w1 + 
                            v2.transformed_normal * w2 + 
                            v3.transformed_normal * w3).normalize();
 
+               let tangent = v1.transformed_tangent * w1 +
+                           v2.transformed_tangent * w2 +
+                           v3.transformed_tangent * w3;
+
                // Interpolate vertex position (for shader effects)
                let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
+               let world_position = v1.world_position * w1 + v2.world_position * w2 + v3.world_position * w3;
+               let tex_coords = v1.tex_coords * w1 + v2.tex_coords * w2 + v3.tex_coords * w3;
 
                // Calculate lighting intensity
-               let intensity = dot(&normal, &light_dir).max(0.0);
+               let intensity = dot(&normal, light_dir).max(0.0);
 
                // Interpolate depth
                let depth = a.z * w1 + b.z * w2 + c.z * w3;
 
-               // Create fragment with all necessary data
-               fragments.push(Fragment::new_with_data(
-                  x as f32,
-                  y as f32,
-                  Color::new(255, 255, 255), // Color will be determined by shader
-                  depth,
-                  normal,
-                  vertex_position,
-                  intensity,
-               ));
+               // Create fragment with all necessary data (color is determined by the shader)
+               fragments.push(
+                  Fragment::default()
+                     .with_position(x as f32, y as f32)
+                     .with_depth(depth)
+                     .with_normal(normal)
+                     .with_tangent(tangent)
+                     .with_vertex_position(vertex_position)
+                     .with_world_position(world_position)
+                     .with_tex_coords(tex_coords)
+                     .with_intensity(intensity)
+               );
          }
       }
    }
@@ -76,4 +82,29 @@ fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) ->
 
 fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use nalgebra_glm::Vec2;
+
+   // Triángulo en pantalla con todas las normales mirando a la cámara (+z)
+   fn facing_triangle() -> [Vertex; 3] {
+      let normal = Vec3::new(0.0, 0.0, 1.0);
+      [(0.0, 0.0), (8.0, 0.0), (0.0, 8.0)].map(|(x, y)| Vertex::new(Vec3::new(x, y, 0.0), normal, Vec2::zeros()))
+   }
+
+   #[test]
+   fn intensity_follows_the_given_light() {
+      let [a, b, c] = facing_triangle();
+      let front = triangle(&a, &b, &c, &Vec3::new(0.0, 0.0, 1.0));
+      let side = triangle(&a, &b, &c, &Vec3::new(1.0, 0.0, 0.0));
+      let tilted = triangle(&a, &b, &c, &Vec3::new(0.0, 1.0, 1.0).normalize());
+
+      assert!(!front.is_empty());
+      assert!(front.iter().all(|fragment| (fragment.intensity - 1.0).abs() < 1e-5));
+      assert!(side.iter().all(|fragment| fragment.intensity.abs() < 1e-5));
+      assert!(tilted.iter().all(|fragment| (fragment.intensity - 0.5f32.sqrt()).abs() < 1e-5));
+   }
+}
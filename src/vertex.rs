@@ -5,32 +5,56 @@ use crate::color::Color;
 pub struct Vertex {
 pub position: Vec3,
 pub normal: Vec3,
+// Dirección en que crece u sobre la superficie, perpendicular a la normal (para normal maps)
+pub tangent: Vec3,
 pub tex_coords: Vec2,
 pub color: Color,
 pub transformed_position: Vec3,
 pub transformed_normal: Vec3,
+pub transformed_tangent: Vec3,
+pub world_position: Vec3,
+}
+
+// Tangente por defecto: la dirección "este" de una esfera (la de u en uv_sphere),
+// que es perpendicular a cualquier normal
+pub fn default_tangent(normal: Vec3) -> Vec3 {
+   let up = if normal.y.abs() < 0.99 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+   let tangent = normal.cross(&up);
+   if tangent.norm_squared() > 0.0 { tangent.normalize() } else { Vec3::new(1.0, 0.0, 0.0) }
 }
 
 impl Vertex {
 pub fn new(position: Vec3, normal: Vec3, tex_coords: Vec2) -> Self {
+   let tangent = default_tangent(normal);
    Vertex {
    position,
    normal,
+   tangent,
    tex_coords,
    color: Color::black(),
    transformed_position: position,
    transformed_normal: normal,
+   transformed_tangent: tangent,
+   world_position: position,
    }
 }
 
+// Completa la base tangente: perpendicular a la normal y a la tangente ya transformadas
+pub fn bitangent(&self) -> Vec3 {
+   self.transformed_normal.cross(&self.transformed_tangent)
+}
+
 pub fn new_with_color(position: Vec3, color: Color) -> Self {
    Vertex {
    position,
    normal: Vec3::new(0.0, 0.0, 0.0),
+   tangent: Vec3::new(1.0, 0.0, 0.0),
    tex_coords: Vec2::new(0.0, 0.0),
    color,
    transformed_position: Vec3::new(0.0, 0.0, 0.0),
    transformed_normal: Vec3::new(0.0, 0.0, 0.0),
+   transformed_tangent: Vec3::new(1.0, 0.0, 0.0),
+   world_position: position,
    }
 }
 
@@ -45,10 +69,13 @@ fn default() -> Self {
    Vertex {
    position: Vec3::new(0.0, 0.0, 0.0),
    normal: Vec3::new(0.0, 1.0, 0.0),
+   tangent: Vec3::new(1.0, 0.0, 0.0),
    tex_coords: Vec2::new(0.0, 0.0),
    color: Color::black(),
    transformed_position: Vec3::new(0.0, 0.0, 0.0),
    transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+   transformed_tangent: Vec3::new(1.0, 0.0, 0.0),
+   world_position: Vec3::new(0.0, 0.0, 0.0),
    }
 }
 }
@@ -0,0 +1,59 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use nalgebra_glm::{Mat4, Vec3};
+use space_renderer::celestial_body::ShaderType;
+use space_renderer::fragment::Fragment;
+use space_renderer::shaders::{fragment_shader, FrameContext, Uniforms};
+
+// Cuenta las reservas de memoria de todo el binario de este test
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+   unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+      ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+      unsafe { System.alloc(layout) }
+   }
+
+   unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+      unsafe { System.dealloc(ptr, layout) }
+   }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn shading_fragments_does_not_allocate() {
+   let uniforms = Uniforms::new(Mat4::identity(), Mat4::identity(), Mat4::identity(), Mat4::identity(), 1.5);
+   let ctx = FrameContext::new(&uniforms);
+   let fragments: Vec<Fragment> = (0..200)
+      .map(|i| {
+         let angle = i as f32 * 0.37;
+         let position = Vec3::new(angle.cos(), (i as f32 / 100.0) - 1.0, angle.sin()).normalize();
+         Fragment::default().with_vertex_position(position).with_normal(position).with_intensity(0.7)
+      })
+      .collect();
+   let shaders = [
+      ShaderType::Sun,
+      ShaderType::RockyPlanet,
+      ShaderType::GasGiant,
+      ShaderType::Moon,
+      ShaderType::RingedPlanet,
+   ];
+
+   // El contexto y los fragmentos ya están armados: sombrear no debería reservar nada
+   let before = ALLOCATIONS.load(Ordering::SeqCst);
+   let mut checksum = 0u32;
+   for shader in &shaders {
+      for fragment in &fragments {
+         checksum = checksum.wrapping_add(fragment_shader(fragment, &ctx, shader).to_hex());
+      }
+   }
+   let allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+
+   assert_ne!(checksum, 0);
+   assert_eq!(allocations, 0);
+}
@@ -0,0 +1,52 @@
+use nalgebra_glm::Vec3;
+use space_renderer::camera::Camera;
+use space_renderer::celestial_body::ShaderType;
+use space_renderer::color::Color;
+use space_renderer::framebuffer::Framebuffer;
+use space_renderer::scene::{shade_scene, BodyInstance, Scene};
+
+const WIDTH: usize = 120;
+const HEIGHT: usize = 80;
+
+fn pixel(framebuffer: &Framebuffer, x: usize, y: usize) -> Color {
+   Color::from_hex(framebuffer.buffer[y * WIDTH + x])
+}
+
+// Cantidad de píxeles distintos del fondo en una región de la imagen
+fn covered(framebuffer: &Framebuffer, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) -> usize {
+   ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+      .filter(|(x, y)| framebuffer.buffer[y * WIDTH + x] != 0)
+      .count()
+}
+
+#[test]
+fn two_bodies_land_in_their_screen_regions() {
+   let scene = Scene {
+      bodies: vec![
+         BodyInstance::new(ShaderType::Sun, Vec3::new(-2.5, 0.0, 0.0), 1.0),
+         BodyInstance::new(ShaderType::Moon, Vec3::new(2.5, 0.0, 0.0), 1.0),
+      ],
+      subdivisions: 16,
+      ..Scene::default()
+   };
+   let camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+   let framebuffer = shade_scene(&scene, &camera, 0.0, WIDTH, HEIGHT);
+
+   // Con 45° de campo vertical y aspecto 1.5, x = ±2.5 a 10 de distancia cae a
+   // unos 24 píxeles del centro, con un radio de casi 10 píxeles
+   let (left, right, middle) = (WIDTH / 2 - 24, WIDTH / 2 + 24, HEIGHT / 2);
+   let sun = pixel(&framebuffer, left, middle);
+   let moon = pixel(&framebuffer, right, middle);
+   let sun_hex = sun.to_hex();
+   assert!((sun_hex >> 16) & 0xFF > (sun_hex & 0xFF) + 40, "left body is not the sun: {sun:?}");
+   assert!(moon.luminance() > 0.05, "right body is missing: {moon:?}");
+   let moon_hex = moon.to_hex();
+   assert!(((moon_hex >> 16) & 0xFF).abs_diff(moon_hex & 0xFF) < 20, "right body is not the moon: {moon:?}");
+
+   // Entre los dos cuerpos y en los bordes solo queda el fondo
+   assert_eq!(covered(&framebuffer, WIDTH / 2 - 4..WIDTH / 2 + 4, 0..HEIGHT), 0);
+   assert_eq!(covered(&framebuffer, 0..WIDTH, 0..8), 0);
+   assert_eq!(covered(&framebuffer, 0..WIDTH, HEIGHT - 8..HEIGHT), 0);
+   assert!(covered(&framebuffer, 0..WIDTH / 2, 0..HEIGHT) > 100);
+   assert!(covered(&framebuffer, WIDTH / 2..WIDTH, 0..HEIGHT) > 100);
+}